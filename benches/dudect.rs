@@ -0,0 +1,213 @@
+// Timing leakage of the block cipher backends and the PKCS#7 unpad check, dudect style
+// https://eprint.iacr.org/2016/1123.pdf
+//
+// Each target is timed on two input classes, a fixed input and random inputs, interleaved
+// at random. Welch's t-test compares the two timing distributions, on all measurements and
+// on measurements cropped at a few percentiles to drop interrupts and other noise.
+// A max |t| above the threshold means timing depends on the data
+//
+// $> cargo bench --bench dudect
+// $> DUDECT_SAMPLES=2000000 DUDECT_THRESHOLD=4.5 cargo bench --bench dudect
+use aes::libaes::KeySchedule;
+use aes::modes::pkcs7;
+use aes::{Key, KeySize};
+use std::hint::black_box;
+use std::process::ExitCode;
+
+// Measurements per target and the |t| that fails the run (dudect's "definitely not constant time")
+const SAMPLES: usize = 500_000;
+const THRESHOLD: f64 = 10.0;
+
+// Measurements discarded while caches and branch predictors settle
+const WARMUP: usize = 10_000;
+
+// Percentiles the measurements are cropped at, in addition to none
+const CROPS: [f64; 3] = [0.5, 0.75, 0.9];
+
+// Cycle counter where there is one, otherwise nanoseconds
+#[cfg(target_arch = "x86_64")]
+fn ticks() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn ticks() -> u64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+// xorshift64*, plenty for choosing classes and random inputs
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next().to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+// Welch's t-test accumulated online (Welford's mean and variance) for classes 0 and 1
+#[derive(Default)]
+struct Welch {
+    n: [f64; 2],
+    mean: [f64; 2],
+    m2: [f64; 2],
+}
+
+impl Welch {
+    fn push(&mut self, class: usize, x: f64) {
+        self.n[class] += 1.0;
+        let delta = x - self.mean[class];
+        self.mean[class] += delta / self.n[class];
+        self.m2[class] += delta * (x - self.mean[class]);
+    }
+
+    fn t(&self) -> f64 {
+        let var = |c: usize| self.m2[c] / (self.n[c] - 1.0);
+        let t = (self.mean[0] - self.mean[1]) / (var(0) / self.n[0] + var(1) / self.n[1]).sqrt();
+        match t.is_nan() {
+            true => 0.0,
+            false => t,
+        }
+    }
+}
+
+// Time "op" on class 0 (the fixed input) and class 1 (random inputs) and return the max |t|
+//
+// Inputs are prepared up front so only "op" falls between the two ticks()
+fn max_t<T>(rng: &mut Rng, samples: usize, fixed: T, random: impl Fn(&mut Rng) -> T, op: impl Fn(&mut T)) -> f64
+where
+    T: Clone,
+{
+    let mut inputs: Vec<(usize, T)> = (0..WARMUP + samples)
+        .map(|_| match rng.next() & 1 {
+            0 => (0, fixed.clone()),
+            _ => (1, random(rng)),
+        })
+        .collect();
+
+    let mut times = Vec::with_capacity(samples);
+    for (i, (class, input)) in inputs.iter_mut().enumerate() {
+        let start = ticks();
+        op(black_box(input));
+        let elapsed = ticks().wrapping_sub(start);
+        if i >= WARMUP {
+            times.push((*class, elapsed as f64));
+        }
+    }
+
+    let mut sorted: Vec<f64> = times.iter().map(|&(_, t)| t).collect();
+    sorted.sort_by(f64::total_cmp);
+    let limits = CROPS.iter().map(|p| sorted[(p * sorted.len() as f64) as usize]);
+
+    std::iter::once(f64::INFINITY)
+        .chain(limits)
+        .map(|limit| {
+            let mut welch = Welch::default();
+            for &(class, t) in times.iter().filter(|&&(_, t)| t <= limit) {
+                welch.push(class, t);
+            }
+            welch.t().abs()
+        })
+        .fold(0.0, f64::max)
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+fn main() -> ExitCode {
+    let samples = env_or("DUDECT_SAMPLES", SAMPLES);
+    let threshold = env_or("DUDECT_THRESHOLD", THRESHOLD);
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    let mut leaks = 0;
+
+    let mut report = |name: &str, t: f64| {
+        let verdict = match t > threshold {
+            true => {
+                leaks += 1;
+                "LEAK"
+            }
+            false => "ok",
+        };
+        println!("{name:<32} max |t| {t:8.2}  {verdict}");
+    };
+
+    for size in [KeySize::Aes128, KeySize::Aes192, KeySize::Aes256] {
+        let bits = size.bits();
+        let mut passkey = [0u8; 32];
+        rng.fill(&mut passkey);
+        let key = KeySchedule::new(&Key::new(size, &passkey[..size.bytes()]).expect("size.bytes() of key"));
+
+        // Fixed key vs random keys
+        let t = max_t(
+            &mut rng,
+            samples,
+            Key::new(size, &[0u8; 32][..size.bytes()]).expect("size.bytes() of key"),
+            |rng| {
+                let mut bytes = [0u8; 32];
+                rng.fill(&mut bytes);
+                Key::new(size, &bytes[..size.bytes()]).expect("size.bytes() of key")
+            },
+            |key| {
+                black_box(KeySchedule::new(key));
+            },
+        );
+        report(&format!("AES-{bits} key expansion"), t);
+
+        // Single block, all-zero block vs random blocks
+        let random_block = |rng: &mut Rng| {
+            let mut block = [0u8; 16];
+            rng.fill(&mut block);
+            block
+        };
+        let t = max_t(&mut rng, samples, [0u8; 16], random_block, |b| key.encrypt_block(b));
+        report(&format!("AES-{bits} encrypt_block"), t);
+        let t = max_t(&mut rng, samples, [0u8; 16], random_block, |b| key.decrypt_block(b));
+        report(&format!("AES-{bits} decrypt_block"), t);
+
+        // Interleaved pipeline, 4 blocks per round iteration
+        let random_blocks = |rng: &mut Rng| {
+            let mut blocks = [0u8; 64];
+            rng.fill(&mut blocks);
+            blocks
+        };
+        let t = max_t(&mut rng, samples, [0u8; 64], random_blocks, |b| key.encrypt_blocks(b));
+        report(&format!("AES-{bits} encrypt_blocks (4 lanes)"), t);
+        let t = max_t(&mut rng, samples, [0u8; 64], random_blocks, |b| key.decrypt_blocks(b));
+        report(&format!("AES-{bits} decrypt_blocks (4 lanes)"), t);
+    }
+
+    // Final decrypted block, a full block of valid padding vs random (almost always invalid)
+    let t = max_t(
+        &mut rng,
+        samples,
+        [16u8; 16],
+        |rng| {
+            let mut block = [0u8; 16];
+            rng.fill(&mut block);
+            block
+        },
+        |block| {
+            black_box(pkcs7::padcount(block));
+        },
+    );
+    report("PKCS#7 padcount", t);
+
+    println!("{samples} samples per target, threshold |t| > {threshold}");
+    match leaks {
+        0 => ExitCode::SUCCESS,
+        _ => ExitCode::FAILURE,
+    }
+}
@@ -0,0 +1,83 @@
+// Throughput of the single-block loop vs the interleaved multi-block pipeline
+//
+// $> cargo bench --bench throughput
+use aes::batch::aes_encrypt_batch;
+use aes::libaes::{aes_encrypt, aes_encrypt_in_place, KeySchedule};
+use aes::{Cipher, Key, KeySize};
+use std::hint::black_box;
+use std::time::Instant;
+
+const MIB: usize = 1024 * 1024;
+const SIZE: usize = 16 * MIB;
+
+// Small records for aes_encrypt() per record vs one batch
+const RECORDS: usize = 1 << 18;
+const RECORD_SIZE: usize = 24;
+
+// Best of a few runs, reported as MiB/s
+fn mib_per_sec(buf: &mut [u8], f: impl Fn(&mut [u8])) -> f64 {
+    let mut best = f64::MAX;
+    for _ in 0..5 {
+        let start = Instant::now();
+        f(black_box(&mut *buf));
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+    (buf.len() / MIB) as f64 / best
+}
+
+// Best of a few runs, reported as RECORDS per second
+fn records_per_sec(f: impl Fn()) -> f64 {
+    let mut best = f64::MAX;
+    for _ in 0..5 {
+        let start = Instant::now();
+        f();
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+    RECORDS as f64 / best
+}
+
+fn main() {
+    let mut buf = vec![0x5a; SIZE];
+    let iv = [0u8; 16];
+
+    for size in [KeySize::Aes128, KeySize::Aes192, KeySize::Aes256] {
+        let bits = size.bits();
+        let passkey = Key::new(size, &[0x2b; 32][..size.bytes()]).expect("size.bytes() of key");
+        let key = KeySchedule::new(&passkey);
+
+        let single = mib_per_sec(&mut buf, |b| {
+            for block in b.chunks_exact_mut(16) {
+                key.encrypt_block(block.try_into().expect("16-byte block"));
+            }
+        });
+        let pipeline = mib_per_sec(&mut buf, |b| key.encrypt_blocks(b));
+        let ctr = mib_per_sec(&mut buf, |b| {
+            aes_encrypt_in_place(&passkey, b, b.len(), &Cipher::CTR, &iv).expect("CTR has room");
+        });
+
+        println!(
+            "AES-{bits}  ECB single-block {single:8.1} MiB/s  ECB pipeline {pipeline:8.1} MiB/s  ({:.2}x)  CTR {ctr:8.1} MiB/s",
+            pipeline / single
+        );
+    }
+
+    let data = vec![0x5a; RECORDS * RECORD_SIZE];
+    let records: Vec<([u8; 16], &[u8])> = data.chunks_exact(RECORD_SIZE).map(|r| (iv, r)).collect();
+    let passkey = Key::from([0x2b; 16]);
+    for cipher in [Cipher::CBC, Cipher::CTR] {
+        let per_record = records_per_sec(|| {
+            for (iv, record) in &records {
+                black_box(aes_encrypt(&passkey, record, &cipher, iv));
+            }
+        });
+        let batch = records_per_sec(|| {
+            black_box(aes_encrypt_batch(&passkey, &records, &cipher));
+        });
+        println!(
+            "AES-128  {RECORDS} {RECORD_SIZE}-byte {cipher:?} records  aes_encrypt {:6.2} M/s  batch {:6.2} M/s  ({:.2}x)",
+            per_record / 1e6,
+            batch / 1e6,
+            batch / per_record
+        );
+    }
+}
@@ -0,0 +1,72 @@
+/*
+ * C interface to the Rust aes crate: libaes.a / libaes.so from
+ *
+ *     cargo build --release -p aes-capi
+ *
+ * Function names and argument order follow Old-C/lib/libaes.c.  Instead of void every
+ * function returns the number of output bytes (>= 0) or a negative AES_ERR_* code.
+ *
+ *   - passkey holds bits/8 bytes, iv holds 16 bytes (NULL for all zeros)
+ *   - data and output may be the same buffer
+ *   - encrypt output needs room for aesPaddedLen(mode, nbytes) bytes, ECB/CBC are
+ *     PKCS#7 padded (a full pad block when nbytes is a multiple of 16)
+ *   - decrypt leaves the PKCS#7 padding in place, see aesUnpad()
+ */
+#ifndef AES_H
+#define AES_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define ECB 0
+#define CBC 1
+#define CTR 2
+
+#define BLKSZ 16
+
+#define AES_ERR_NULL    -1  /* a required pointer is NULL */
+#define AES_ERR_BITS    -2  /* bits not in [128, 192, 256] or esize doesn't match */
+#define AES_ERR_MODE    -3  /* mode not in [ECB, CBC, CTR] */
+#define AES_ERR_LENGTH  -4  /* negative nbytes, or ECB/CBC ciphertext not a multiple of 16 */
+#define AES_ERR_PADDING -5  /* bad PKCS#7 padding */
+#define AES_ERR_KDF     -6  /* key derivation failed */
+#define AES_ERR_PANIC   -7  /* internal error */
+
+typedef unsigned char uchar;
+
+/* Old-C call shapes, esize is one of (176, 208, or 240) matching bits */
+int aes128Encrypt (const uchar *passkey, const uchar *data, int nbytes, int mode, const uchar *iv, uchar *output);
+int aes192Encrypt (const uchar *passkey, const uchar *data, int nbytes, int mode, const uchar *iv, uchar *output);
+int aes256Encrypt (const uchar *passkey, const uchar *data, int nbytes, int mode, const uchar *iv, uchar *output);
+int aes128Decrypt (const uchar *passkey, const uchar *data, int nbytes, int mode, const uchar *iv, uchar *output);
+int aes192Decrypt (const uchar *passkey, const uchar *data, int nbytes, int mode, const uchar *iv, uchar *output);
+int aes256Decrypt (const uchar *passkey, const uchar *data, int nbytes, int mode, const uchar *iv, uchar *output);
+int aesEncrypt    (int bits, int esize, const uchar *passkey, const uchar *data, int nbytes, int mode, const uchar *iv, uchar *output);
+int aesDecrypt    (int bits, int esize, const uchar *passkey, const uchar *data, int nbytes, int mode, const uchar *iv, uchar *output);
+int aesEncryptCTR (int bits, int esize, const uchar *passkey, const uchar *data, int nbytes, const uchar *iv, uchar *output);
+
+/* Key setup once for many calls, NULL on a bad bits or NULL passkey */
+typedef struct AesKey AesKey;
+
+AesKey *aesKeySetup   (int bits, const uchar *passkey);
+void    aesKeyFree    (AesKey *key);
+int     aesKeyEncrypt (const AesKey *key, const uchar *data, int nbytes, int mode, const uchar *iv, uchar *output);
+int     aesKeyDecrypt (const AesKey *key, const uchar *data, int nbytes, int mode, const uchar *iv, uchar *output);
+
+/* Ciphertext size of nbytes, and the plaintext size of decrypted ECB/CBC data */
+int aesPaddedLen (int mode, int nbytes);
+int aesUnpad     (const uchar *data, int nbytes);
+
+/* Key derivation writing a bits/8-byte key (zero filled to 32 bytes) and 16-byte iv, returns 0 on success */
+int aesPbkdf2 (int bits, const uchar *password, int passlen, const uchar *salt, int saltlen, unsigned int iter, uchar *key, uchar *iv);
+int aesArgon2 (int bits, const uchar *password, int passlen, const uchar *salt, int saltlen, uchar *key, uchar *iv);
+
+/* Static description of an AES_ERR_* code */
+const char *aesStrError (int code);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* AES_H */
@@ -0,0 +1,433 @@
+// C ABI over the aes crate, see include/aes.h
+//
+// The function names and argument order follow Old-C/lib/libaes.c so existing callers
+// only need to relink.  Instead of void, every function returns the number of output
+// bytes or a negative AES_ERR_* code, nothing panics across the boundary.
+#![allow(non_snake_case)]
+#![allow(clippy::missing_safety_doc)]
+
+use aes_core::kdf::Kdf;
+use aes_core::libaes::{padded_len, KeySchedule};
+use aes_core::modes::pkcs7;
+use aes_core::{AesError, Cipher, Key, KeySize};
+use std::ffi::{c_char, c_int, c_uint};
+use std::panic::{catch_unwind, UnwindSafe};
+use std::{ptr, slice};
+
+// Error codes, keep in sync with include/aes.h
+pub const AES_ERR_NULL: c_int = -1; // a required pointer is NULL
+pub const AES_ERR_BITS: c_int = -2; // bits not in [128, 192, 256] or esize doesn't match
+pub const AES_ERR_MODE: c_int = -3; // mode not in [ECB, CBC, CTR]
+pub const AES_ERR_LENGTH: c_int = -4; // negative nbytes, or ECB/CBC ciphertext not a multiple of 16
+pub const AES_ERR_PADDING: c_int = -5; // bad PKCS#7 padding
+pub const AES_ERR_KDF: c_int = -6; // key derivation failed
+pub const AES_ERR_PANIC: c_int = -7; // internal error
+
+// Old-C mode values
+const ECB: c_int = 0;
+const CBC: c_int = 1;
+const CTR: c_int = 2;
+
+// Opaque key schedule handle for repeated calls with the same key
+pub struct AesKey {
+    key: KeySchedule,
+}
+
+// Run "f" returning AES_ERR_PANIC instead of unwinding into C
+fn guard(f: impl FnOnce() -> Result<c_int, c_int> + UnwindSafe) -> c_int {
+    match catch_unwind(f) {
+        Ok(Ok(n)) | Ok(Err(n)) => n,
+        Err(_) => AES_ERR_PANIC,
+    }
+}
+
+// The AES_ERR_* code for a library error
+fn errno(e: AesError) -> c_int {
+    match e {
+        AesError::InvalidKeyLength(_) | AesError::KeySizeMismatch { .. } => AES_ERR_BITS,
+        AesError::InvalidCiphertextLength(_) | AesError::BufferTooSmall { .. } => AES_ERR_LENGTH,
+        AesError::BadPadding => AES_ERR_PADDING,
+        AesError::KdfError(_) => AES_ERR_KDF,
+        _ => AES_ERR_PANIC,
+    }
+}
+
+fn cipher(mode: c_int) -> Result<Cipher, c_int> {
+    match mode {
+        ECB => Ok(Cipher::ECB),
+        CBC => Ok(Cipher::CBC),
+        CTR => Ok(Cipher::CTR),
+        _ => Err(AES_ERR_MODE),
+    }
+}
+
+// The expanded key size Old-C callers pass along with bits
+fn esize(bits: c_int) -> Result<c_int, c_int> {
+    match bits {
+        128 => Ok(176),
+        192 => Ok(208),
+        256 => Ok(240),
+        _ => Err(AES_ERR_BITS),
+    }
+}
+
+fn length(nbytes: c_int) -> Result<usize, c_int> {
+    usize::try_from(nbytes).map_err(|_| AES_ERR_LENGTH)
+}
+
+fn key_size(bits: c_int) -> Result<KeySize, c_int> {
+    esize(bits)?;
+    KeySize::from_bits(bits as usize).map_err(errno)
+}
+
+// The first bits/8 bytes of "passkey"
+unsafe fn key(bits: c_int, passkey: *const u8) -> Result<Key, c_int> {
+    let size = key_size(bits)?;
+    if passkey.is_null() {
+        return Err(AES_ERR_NULL);
+    }
+    Key::new(size, slice::from_raw_parts(passkey, size.bytes())).map_err(errno)
+}
+
+// A NULL iv is all zeros, as in Old-C
+unsafe fn iv16(iv: *const u8) -> [u8; 16] {
+    match iv.is_null() {
+        true => [0; 16],
+        false => *(iv as *const [u8; 16]),
+    }
+}
+
+// Copy "data" to "output" (they may overlap) and return "output" as a slice of "len" bytes
+unsafe fn copy_to_output<'a>(
+    data: *const u8,
+    nbytes: usize,
+    output: *mut u8,
+    len: usize,
+) -> Result<&'a mut [u8], c_int> {
+    if data.is_null() || output.is_null() {
+        return Err(AES_ERR_NULL);
+    }
+    ptr::copy(data, output, nbytes);
+    Ok(slice::from_raw_parts_mut(output, len))
+}
+
+// Encrypt "nbytes" of "data" to "output", which has room for aesPaddedLen(mode, nbytes) bytes
+unsafe fn encrypt(
+    key: &KeySchedule,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> Result<c_int, c_int> {
+    let cipher = cipher(mode)?;
+    let nbytes = length(nbytes)?;
+    let len = padded_len(&cipher, nbytes);
+    let output = copy_to_output(data, nbytes, output, len)?;
+    let n = key
+        .encrypt_in_place(output, nbytes, &cipher, &iv16(iv))
+        .map_err(errno)?;
+    c_int::try_from(n).map_err(|_| AES_ERR_LENGTH)
+}
+
+// Decrypt "nbytes" of "data" to "output", the PKCS#7 padding is left in place (see aesUnpad)
+unsafe fn decrypt(
+    key: &KeySchedule,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> Result<c_int, c_int> {
+    let cipher = cipher(mode)?;
+    let len = length(nbytes)?;
+    if cipher != Cipher::CTR && len % 16 != 0 {
+        return Err(AES_ERR_LENGTH);
+    }
+    let output = copy_to_output(data, len, output, len)?;
+    key.decrypt_in_place(output, &cipher, &iv16(iv)).map_err(errno)?;
+    Ok(nbytes)
+}
+
+// ==================
+// Old-C call shapes
+// ==================
+#[no_mangle]
+pub unsafe extern "C" fn aesEncrypt(
+    bits: c_int,
+    esize_: c_int,
+    passkey: *const u8,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    guard(|| {
+        if esize(bits)? != esize_ {
+            return Err(AES_ERR_BITS);
+        }
+        encrypt(&KeySchedule::new(&key(bits, passkey)?), data, nbytes, mode, iv, output)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aesDecrypt(
+    bits: c_int,
+    esize_: c_int,
+    passkey: *const u8,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    guard(|| {
+        if esize(bits)? != esize_ {
+            return Err(AES_ERR_BITS);
+        }
+        decrypt(&KeySchedule::new(&key(bits, passkey)?), data, nbytes, mode, iv, output)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aesEncryptCTR(
+    bits: c_int,
+    esize: c_int,
+    passkey: *const u8,
+    data: *const u8,
+    nbytes: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    aesEncrypt(bits, esize, passkey, data, nbytes, CTR, iv, output)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aes128Encrypt(
+    passkey: *const u8,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    aesEncrypt(128, 176, passkey, data, nbytes, mode, iv, output)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aes192Encrypt(
+    passkey: *const u8,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    aesEncrypt(192, 208, passkey, data, nbytes, mode, iv, output)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aes256Encrypt(
+    passkey: *const u8,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    aesEncrypt(256, 240, passkey, data, nbytes, mode, iv, output)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aes128Decrypt(
+    passkey: *const u8,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    aesDecrypt(128, 176, passkey, data, nbytes, mode, iv, output)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aes192Decrypt(
+    passkey: *const u8,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    aesDecrypt(192, 208, passkey, data, nbytes, mode, iv, output)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aes256Decrypt(
+    passkey: *const u8,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    aesDecrypt(256, 240, passkey, data, nbytes, mode, iv, output)
+}
+
+// ==========
+// Key setup
+// ==========
+
+// NULL on a bad "bits" or NULL "passkey", release with aesKeyFree()
+#[no_mangle]
+pub unsafe extern "C" fn aesKeySetup(bits: c_int, passkey: *const u8) -> *mut AesKey {
+    match key(bits, passkey) {
+        Ok(key) => Box::into_raw(Box::new(AesKey {
+            key: KeySchedule::new(&key),
+        })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aesKeyFree(key: *mut AesKey) {
+    if !key.is_null() {
+        drop(Box::from_raw(key));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aesKeyEncrypt(
+    key: *const AesKey,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    guard(|| encrypt(&key.as_ref().ok_or(AES_ERR_NULL)?.key, data, nbytes, mode, iv, output))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn aesKeyDecrypt(
+    key: *const AesKey,
+    data: *const u8,
+    nbytes: c_int,
+    mode: c_int,
+    iv: *const u8,
+    output: *mut u8,
+) -> c_int {
+    guard(|| decrypt(&key.as_ref().ok_or(AES_ERR_NULL)?.key, data, nbytes, mode, iv, output))
+}
+
+// ========
+// Padding
+// ========
+
+// Bytes of ciphertext produced by encrypting "nbytes"
+#[no_mangle]
+pub extern "C" fn aesPaddedLen(mode: c_int, nbytes: c_int) -> c_int {
+    guard(|| {
+        let n = padded_len(&cipher(mode)?, length(nbytes)?);
+        c_int::try_from(n).map_err(|_| AES_ERR_LENGTH)
+    })
+}
+
+// Length of decrypted ECB/CBC "data" once the PKCS#7 padding is removed
+#[no_mangle]
+pub unsafe extern "C" fn aesUnpad(data: *const u8, nbytes: c_int) -> c_int {
+    guard(|| {
+        let len = length(nbytes)?;
+        if data.is_null() {
+            return Err(AES_ERR_NULL);
+        }
+        if len == 0 || len % 16 != 0 {
+            return Err(AES_ERR_LENGTH);
+        }
+
+        let block = slice::from_raw_parts(data.add(len - 16), 16);
+        match pkcs7::padcount(block) {
+            Some(padcount) => Ok(nbytes - padcount as c_int),
+            None => Err(AES_ERR_PADDING),
+        }
+    })
+}
+
+// =============================
+// Key Derivation, see src/kdf.rs
+// =============================
+#[allow(clippy::too_many_arguments)]
+unsafe fn keyiv(
+    kdf: Kdf,
+    bits: c_int,
+    password: *const u8,
+    passlen: c_int,
+    salt: *const u8,
+    saltlen: c_int,
+    key: *mut u8,
+    iv: *mut u8,
+) -> Result<c_int, c_int> {
+    let size = key_size(bits)?;
+    if password.is_null() || salt.is_null() || key.is_null() || iv.is_null() {
+        return Err(AES_ERR_NULL);
+    }
+    let password = slice::from_raw_parts(password, length(passlen)?);
+    let salt = slice::from_raw_parts(salt, length(saltlen)?);
+
+    // bits/8 bytes of key, zero filled to 32
+    let (k, i) = kdf.keyiv(size, password, salt).map_err(errno)?;
+    ptr::write_bytes(key, 0, 32);
+    ptr::copy_nonoverlapping(k.as_bytes().as_ptr(), key, k.as_bytes().len());
+    ptr::copy_nonoverlapping(i.as_ptr(), iv, 16);
+    Ok(0)
+}
+
+// PBKDF2-HMAC-SHA256, OpenSSL -pbkdf2 compatible: writes a bits/8-byte key (zero filled to 32) and 16-byte iv
+#[no_mangle]
+pub unsafe extern "C" fn aesPbkdf2(
+    bits: c_int,
+    password: *const u8,
+    passlen: c_int,
+    salt: *const u8,
+    saltlen: c_int,
+    iter: c_uint,
+    key: *mut u8,
+    iv: *mut u8,
+) -> c_int {
+    guard(|| keyiv(Kdf::PBKDF2(iter), bits, password, passlen, salt, saltlen, key, iv))
+}
+
+// Argon2id with default parameters: writes a bits/8-byte key (zero filled to 32) and 16-byte iv
+#[no_mangle]
+pub unsafe extern "C" fn aesArgon2(
+    bits: c_int,
+    password: *const u8,
+    passlen: c_int,
+    salt: *const u8,
+    saltlen: c_int,
+    key: *mut u8,
+    iv: *mut u8,
+) -> c_int {
+    guard(|| keyiv(Kdf::ARGON2, bits, password, passlen, salt, saltlen, key, iv))
+}
+
+// Static description of an AES_ERR_* code
+#[no_mangle]
+pub extern "C" fn aesStrError(code: c_int) -> *const c_char {
+    let s: &'static [u8] = match code {
+        AES_ERR_NULL => b"NULL pointer argument\0",
+        AES_ERR_BITS => b"bits not in [128, 192, 256]\0",
+        AES_ERR_MODE => b"mode not in [ECB, CBC, CTR]\0",
+        AES_ERR_LENGTH => b"invalid length\0",
+        AES_ERR_PADDING => b"bad PKCS#7 padding\0",
+        AES_ERR_KDF => b"key derivation failed\0",
+        AES_ERR_PANIC => b"internal error\0",
+        n if n >= 0 => b"success\0",
+        _ => b"unknown error\0",
+    };
+    s.as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests;
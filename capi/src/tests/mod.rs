@@ -0,0 +1,131 @@
+use crate::*;
+use aes_core::libaes::aes_encrypt;
+use hex_literal::hex;
+
+const PASSKEY: &[u8; 32] = b"YELLOW SUBMARINEyellow submarine";
+const IV: [u8; 16] = hex!("000102030405060708090a0b0c0d0e0f");
+
+#[test]
+fn test_old_c_call_shapes() {
+    let data: Vec<u8> = (0..50).collect();
+    let encrypt = [aes128Encrypt, aes192Encrypt, aes256Encrypt];
+    let decrypt = [aes128Decrypt, aes192Decrypt, aes256Decrypt];
+
+    for (i, bits) in [128, 192, 256].into_iter().enumerate() {
+        for (mode, cipher) in [(ECB, Cipher::ECB), (CBC, Cipher::CBC), (CTR, Cipher::CTR)] {
+            for len in [0, 15, 16, 50] {
+                let passkey = Key::try_from(&PASSKEY[..bits / 8]).unwrap();
+                let expected = aes_encrypt(&passkey, &data[..len], &cipher, &IV);
+
+                let mut output = [0u8; 64];
+                let n = unsafe {
+                    encrypt[i](
+                        PASSKEY.as_ptr(),
+                        data.as_ptr(),
+                        len as c_int,
+                        mode,
+                        IV.as_ptr(),
+                        output.as_mut_ptr(),
+                    )
+                };
+                assert_eq!(n, aesPaddedLen(mode, len as c_int));
+                assert_eq!(&output[..n as usize], expected);
+
+                // In place, then remove the padding
+                let n = unsafe {
+                    decrypt[i](
+                        PASSKEY.as_ptr(),
+                        output.as_ptr(),
+                        n,
+                        mode,
+                        IV.as_ptr(),
+                        output.as_mut_ptr(),
+                    )
+                };
+                let n = match mode {
+                    CTR => n,
+                    _ => unsafe { aesUnpad(output.as_ptr(), n) },
+                };
+                assert_eq!(&output[..n as usize], &data[..len]);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_key_handle() {
+    let data = b"attack at dawn";
+    let mut expected = [0u8; 16];
+    let mut output = [0u8; 16];
+    unsafe {
+        let key = aesKeySetup(256, PASSKEY.as_ptr());
+        assert!(!key.is_null());
+        assert_eq!(
+            aesKeyEncrypt(key, data.as_ptr(), 14, CBC, IV.as_ptr(), output.as_mut_ptr()),
+            16
+        );
+        aes256Encrypt(
+            PASSKEY.as_ptr(),
+            data.as_ptr(),
+            14,
+            CBC,
+            IV.as_ptr(),
+            expected.as_mut_ptr(),
+        );
+        assert_eq!(output, expected);
+        assert_eq!(
+            aesKeyDecrypt(key, output.as_ptr(), 16, CBC, IV.as_ptr(), output.as_mut_ptr()),
+            16
+        );
+        assert_eq!(aesUnpad(output.as_ptr(), 16), 14);
+        assert_eq!(&output[..14], data);
+        aesKeyFree(key);
+
+        assert!(aesKeySetup(100, PASSKEY.as_ptr()).is_null());
+        assert!(aesKeySetup(128, ptr::null()).is_null());
+    }
+}
+
+#[test]
+fn test_error_codes() {
+    let data = [0u8; 32];
+    let mut output = [0u8; 48];
+    let (p, d, o) = (PASSKEY.as_ptr(), data.as_ptr(), output.as_mut_ptr());
+    unsafe {
+        assert_eq!(aesEncrypt(100, 176, p, d, 16, ECB, ptr::null(), o), AES_ERR_BITS);
+        assert_eq!(aesEncrypt(128, 208, p, d, 16, ECB, ptr::null(), o), AES_ERR_BITS);
+        assert_eq!(aes128Encrypt(p, d, 16, 3, ptr::null(), o), AES_ERR_MODE);
+        assert_eq!(aes128Encrypt(p, d, -1, ECB, ptr::null(), o), AES_ERR_LENGTH);
+        assert_eq!(aes128Encrypt(ptr::null(), d, 16, ECB, ptr::null(), o), AES_ERR_NULL);
+        assert_eq!(aes128Encrypt(p, d, 16, ECB, ptr::null(), ptr::null_mut()), AES_ERR_NULL);
+        assert_eq!(aes128Decrypt(p, d, 17, CBC, ptr::null(), o), AES_ERR_LENGTH);
+        assert_eq!(aesPaddedLen(ECB, -5), AES_ERR_LENGTH);
+
+        // All zero data decrypts to a block ending in 0x00
+        assert_eq!(aesUnpad(d, 32), AES_ERR_PADDING);
+        assert_eq!(aesUnpad(d, 0), AES_ERR_LENGTH);
+
+        let s = std::ffi::CStr::from_ptr(aesStrError(AES_ERR_PADDING));
+        assert_eq!(s.to_str(), Ok("bad PKCS#7 padding"));
+    }
+}
+
+#[test]
+fn test_pbkdf2() {
+    let (mut key, mut iv) = ([0u8; 32], [0u8; 16]);
+    let n = unsafe {
+        aesPbkdf2(
+            128,
+            b"Password".as_ptr(),
+            8,
+            b"NaCl".as_ptr(),
+            4,
+            80_000,
+            key.as_mut_ptr(),
+            iv.as_mut_ptr(),
+        )
+    };
+    assert_eq!(n, 0);
+    assert_eq!(&key[..16], hex!("4ddcd8f60b98be21830cee5ef22701f9"));
+    assert_eq!(iv, hex!("641a4418d04c0414aeff08876b34ab56"));
+}
@@ -89,19 +89,31 @@ pub fn get_args() -> ArgMatches {
         // PKCS#7
         .arg(arg!(--nopkcs "Prevent a full pad block on --encrypt, skip PKCS#7 pad removal on --decrypt"))
 
+        // Self-describing output
+        .arg(arg!(--container "Write a header with the cipher, key size, KDF, salt and iv, --decrypt then needs only the key").conflicts_with_all(["decrypt", "randiv"]))
+        .arg(arg!(--chunked "Authenticated AES-GCM in 64 KiB chunks after a --container header, tampering, truncation and reordering fail --decrypt").conflicts_with_all(["decrypt", "cipher", "aes128", "aes192", "aes256", "iv", "randiv", "nopkcs"]))
+
         // Output cipher details to stderr
         .arg(arg!(P: -P "Print the salt/key/iv and exit"))
 
         // Supress stderr warnings
         .arg(arg!(-q --quiet "Silences warnings regarding short or long passwords"))
 
-        // Input
+        // Input, any number of files and directories (-R)
         .arg(
-            arg!(<FILE> "File to read, treats '-' as standard input")
+            arg!(<FILE> "Files to read, treats '-' as standard input, several are each written to FILE.aes")
                 .required(false)
+                .num_args(0..)
                 .value_parser(value_parser!(PathBuf)),
         )
-        .after_help("Examples:\n  aes -e --aes-256-cbc --argon2 -a -k password file\n  aes -d --aes-256-cbc --argon2 -A -k password file.aes");
+        .arg(arg!(-R --recursive "Read the files in directories"))
+        .arg(arg!(--"out-dir" <DIR> "Write each output below DIR mirroring the inputs").value_parser(value_parser!(PathBuf)).conflicts_with_all(["out", "in-place"]))
+
+        // Output, standard output by default
+        .arg(arg!(-o --out <OUT> "File to write (replaced when complete), treats '-' as standard output").value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--"in-place" "Replace FILE with the output when complete, keeping its permissions").requires("FILE").conflicts_with("out"))
+        .arg(arg!(-f --force "Overwrite existing output files"))
+        .after_help("Examples:\n  aes -e --aes-256-cbc --argon2 -a -k password file\n  aes -d --aes-256-cbc --argon2 -A -k password -o file file.aes\n  aes -e --aes-256-cbc --argon2 -k password -R dir\n  aes -e --chunked --256 --argon2 -k password -o file.aes file");
 
     app.get_matches_from(env::args().collect::<Vec<String>>())
 }
@@ -0,0 +1,181 @@
+use crate::framing::{Framing, KeyIv};
+use crate::key::Key;
+use crate::stream::{Decryptor, Encryptor};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use zeroize::Zeroizing;
+
+// tokio adapters equivalent to io::EncryptingWriter and io::DecryptingReader
+//
+// Both apply backpressure: a write is only accepted once the ciphertext of the previous
+// write has been passed to the inner writer, and a read only reads from the inner reader
+// once all the plaintext decrypted so far has been returned.
+//
+// A KDF runs synchronously inside new() (writer) or the first poll_read() (reader),
+// argon2 in particular may warrant spawn_blocking around key derivation.
+
+// Most plaintext accepted by a single poll_write
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct AsyncEncryptingWriter<W: AsyncWrite + Unpin> {
+    inner: W,
+    enc: Option<Encryptor>,
+    buf: Vec<u8>, // ciphertext not yet written to "inner"
+    pos: usize,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncEncryptingWriter<W> {
+    // The 1st block is written to "inner" along with the first ciphertext
+    //
+    // "ivector" is the supplied iv (or salt with a KDF), it should be random for
+    // --randiv and the KDFs
+    pub fn new(inner: W, framing: &Framing, key: &Key, ivector: &[u8; 16]) -> io::Result<Self> {
+        let (header, (key, ivector)) = framing.encrypt_header(key, ivector)?;
+
+        let enc = Encryptor::new(&key, framing.cipher, &ivector).nopkcs(framing.nopkcs);
+        Ok(Self {
+            inner,
+            enc: Some(enc),
+            buf: header,
+            pos: 0,
+        })
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    // Write out buffered ciphertext
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.pos < self.buf.len() {
+            match ready!(Pin::new(&mut self.inner).poll_write(cx, &self.buf[self.pos..]))? {
+                0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                n => self.pos += n,
+            }
+        }
+        self.buf.clear();
+        self.pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncEncryptingWriter<W> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, data: &[u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        ready!(this.poll_drain(cx))?;
+
+        let Some(enc) = this.enc.as_mut() else {
+            return Poll::Ready(Err(io::Error::other("write after shutdown")));
+        };
+
+        let data = &data[..data.len().min(CHUNK_SIZE)];
+        enc.update_into(data, &mut this.buf);
+
+        // The data is accepted, ciphertext left over is written on the next call
+        let _ = this.poll_drain(cx)?;
+        Poll::Ready(Ok(data.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_drain(cx))?;
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    // Write the final padded block and shut down the inner writer
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        ready!(this.poll_drain(cx))?;
+
+        if let Some(enc) = this.enc.take() {
+            enc.finalize_into(&mut this.buf);
+            ready!(this.poll_drain(cx))?;
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+pub struct AsyncDecryptingReader<R: AsyncRead + Unpin> {
+    inner: R,
+    framing: Framing,
+    keyiv: Option<KeyIv>, // supplied key and iv until the 1st block is read
+    header: Vec<u8>,
+    dec: Option<Decryptor>,
+    buf: Zeroizing<Vec<u8>>, // decrypted bytes not yet returned, wiped on drop
+    pos: usize,
+    chunk: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncDecryptingReader<R> {
+    // The 1st block is read from "inner" on the first poll_read()
+    //
+    // "ivector" is the supplied iv, used when the layout has no 1st block
+    pub fn new(inner: R, framing: &Framing, key: &Key, ivector: &[u8; 16]) -> Self {
+        Self {
+            inner,
+            framing: framing.clone(),
+            keyiv: Some((key.clone(), *ivector)),
+            header: Vec::with_capacity(framing.header_len()),
+            dec: None,
+            buf: Zeroizing::new(vec![]),
+            pos: 0,
+            chunk: vec![0; CHUNK_SIZE],
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Read the 1st block and set up the Decryptor
+    fn poll_header(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.keyiv.is_none() {
+            return Poll::Ready(Ok(()));
+        }
+
+        while self.header.len() < self.framing.header_len() {
+            let mut block = [0u8; 16];
+            let mut rb = ReadBuf::new(&mut block[..self.framing.header_len() - self.header.len()]);
+            ready!(Pin::new(&mut self.inner).poll_read(cx, &mut rb))?;
+            match rb.filled() {
+                [] => return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into())),
+                filled => self.header.extend_from_slice(filled),
+            }
+        }
+
+        let (key, ivector) = self.keyiv.as_ref().expect("checked above");
+        let (key, ivector) = self.framing.decrypt_header(key, ivector, &self.header)?;
+        self.keyiv = None;
+        self.dec = Some(Decryptor::new(&key, self.framing.cipher, &ivector).nopkcs(self.framing.nopkcs));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncDecryptingReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, out: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        ready!(this.poll_header(cx))?;
+
+        while this.pos == this.buf.len() {
+            let Some(dec) = this.dec.as_mut() else {
+                return Poll::Ready(Ok(()));
+            };
+
+            let mut rb = ReadBuf::new(&mut this.chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut rb))?;
+
+            this.buf.clear();
+            this.pos = 0;
+            match rb.filled() {
+                [] => this.dec.take().expect("decryptor").finalize_into(&mut this.buf)?,
+                filled => dec.update_into(filled, &mut this.buf),
+            }
+        }
+
+        let n = out.remaining().min(this.buf.len() - this.pos);
+        out.put_slice(&this.buf[this.pos..this.pos + n]);
+        this.pos += n;
+        Poll::Ready(Ok(()))
+    }
+}
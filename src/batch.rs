@@ -0,0 +1,271 @@
+use crate::error::AesError;
+use crate::key::Key;
+use crate::libaes::{padded_len, KeySchedule, LANES};
+use crate::modes::pkcs7;
+use crate::Cipher;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Index;
+use zeroize::{Zeroize, Zeroizing};
+
+// Many small independent messages under one key
+//
+// The key is expanded once and the output of every record goes into one Arena instead of
+// a Vec per record.  Blocks of different records share the LANES pipeline: ECB runs over the
+// whole batch at once, the CTR keystream is made LANES blocks at a time straight across
+// records, CBC encryption (sequential within a record) steps LANES records in lockstep.
+//
+// Each record is (iv, data), the iv is ignored by ECB.  Results match aes_encrypt() and
+// aes_decrypt() record by record, decryption leaves PKCS#7 pad removal to the caller.
+
+// Results of a batch stored back to back, record i is arena[i]
+//
+// Batches append, clear() to reuse the allocation.  The bytes are wiped on drop, and when
+// growing the old allocation is wiped before it's freed
+#[derive(Clone, Default)]
+pub struct Arena {
+    bytes: Vec<u8>,
+    ends: Vec<usize>,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Room for "records" records totalling "bytes" bytes
+    pub fn with_capacity(records: usize, bytes: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(bytes),
+            ends: Vec::with_capacity(records),
+        }
+    }
+
+    // Number of records
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<&[u8]> {
+        let end = *self.ends.get(i)?;
+        Some(&self.bytes[self.start(i)..end])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        (0..self.len()).map(|i| &self[i])
+    }
+
+    // Every record, back to back
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.zeroize();
+        self.ends.clear();
+    }
+
+    fn start(&self, i: usize) -> usize {
+        match i {
+            0 => 0,
+            _ => self.ends[i - 1],
+        }
+    }
+
+    // Room for "additional" more bytes, moved by hand: a reallocation would free the old
+    // bytes without wiping them
+    fn reserve(&mut self, additional: usize) {
+        let needed = self.bytes.len() + additional;
+        if needed > self.bytes.capacity() {
+            let mut bytes = Vec::with_capacity(needed.max(2 * self.bytes.capacity()));
+            bytes.extend_from_slice(&self.bytes);
+            self.bytes.zeroize();
+            self.bytes = bytes;
+        }
+    }
+
+    // Append a record of "len" bytes starting with "data", returning its range
+    fn push(&mut self, data: &[u8], len: usize) -> (usize, usize) {
+        self.reserve(len);
+        let start = self.bytes.len();
+        self.bytes.extend_from_slice(data);
+        self.bytes.resize(start + len, 0);
+        self.ends.push(start + len);
+        (start, start + len)
+    }
+}
+
+impl Index<usize> for Arena {
+    type Output = [u8];
+
+    fn index(&self, i: usize) -> &[u8] {
+        self.get(i).expect("record index in range")
+    }
+}
+
+// Sizes only, never the bytes
+impl fmt::Debug for Arena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Arena({} records, {} bytes)", self.len(), self.bytes.len())
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+// Encrypt each (iv, plaintext) record, see KeySchedule::encrypt_batch()
+pub fn aes_encrypt_batch(key: &Key, records: &[([u8; 16], &[u8])], cipher: &Cipher) -> Arena {
+    let bytes = records.iter().map(|(_, data)| padded_len(cipher, data.len())).sum();
+    let mut arena = Arena::with_capacity(records.len(), bytes);
+    KeySchedule::new(key).encrypt_batch(records, cipher, &mut arena);
+    arena
+}
+
+// Decrypt each (iv, ciphertext) record, see KeySchedule::decrypt_batch()
+pub fn aes_decrypt_batch(key: &Key, records: &[([u8; 16], &[u8])], cipher: &Cipher) -> Result<Arena, AesError> {
+    let bytes = records.iter().map(|(_, data)| data.len()).sum();
+    let mut arena = Arena::with_capacity(records.len(), bytes);
+    KeySchedule::new(key).decrypt_batch(records, cipher, &mut arena)?;
+    Ok(arena)
+}
+
+impl KeySchedule {
+    // Append the ciphertext of each (iv, plaintext) record to "arena"
+    pub fn encrypt_batch(&self, records: &[([u8; 16], &[u8])], cipher: &Cipher, arena: &mut Arena) {
+        let first = arena.bytes.len();
+        let ranges: Vec<(usize, usize)> = records
+            .iter()
+            .map(|(_, data)| {
+                let (start, end) = arena.push(data, padded_len(cipher, data.len()));
+                if cipher != &Cipher::CTR {
+                    pkcs7::pad(&mut arena.bytes[start..end], data.len());
+                }
+                (start, end)
+            })
+            .collect();
+
+        match cipher {
+            Cipher::ECB => self.encrypt_blocks(&mut arena.bytes[first..]),
+            Cipher::CTR => self.ctr_batch(records, &ranges, &mut arena.bytes),
+            Cipher::CBC => {
+                for (records, ranges) in records.chunks(LANES).zip(ranges.chunks(LANES)) {
+                    self.cbc_lockstep(records, ranges, &mut arena.bytes);
+                }
+            }
+        }
+    }
+
+    // Append the plaintext of each (iv, ciphertext) record to "arena"
+    //
+    // ECB/CBC records must be a multiple of 16 bytes, "arena" is unchanged otherwise
+    pub fn decrypt_batch(
+        &self,
+        records: &[([u8; 16], &[u8])],
+        cipher: &Cipher,
+        arena: &mut Arena,
+    ) -> Result<(), AesError> {
+        if cipher != &Cipher::CTR {
+            if let Some((_, data)) = records.iter().find(|(_, data)| !data.len().is_multiple_of(16)) {
+                return Err(AesError::InvalidCiphertextLength(data.len()));
+            }
+        }
+
+        let first = arena.bytes.len();
+        let ranges: Vec<(usize, usize)> = records.iter().map(|(_, data)| arena.push(data, data.len())).collect();
+
+        match cipher {
+            Cipher::ECB => self.decrypt_blocks(&mut arena.bytes[first..]),
+            Cipher::CTR => self.ctr_batch(records, &ranges, &mut arena.bytes),
+            Cipher::CBC => {
+                // Every block decrypts independently, then XOR the previous ciphertext block
+                self.decrypt_blocks(&mut arena.bytes[first..]);
+                for ((iv, data), &(start, end)) in records.iter().zip(&ranges) {
+                    for (n, block) in arena.bytes[start..end].chunks_exact_mut(16).enumerate() {
+                        let chain = match n {
+                            0 => &iv[..],
+                            _ => &data[16 * (n - 1)..16 * n],
+                        };
+                        block.iter_mut().zip(chain).for_each(|(b, c)| *b ^= c);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // CTR over every record: counter blocks fill a window of LANES blocks, crossing record
+    // boundaries, which is encrypted and XOR'd into the records in place
+    //
+    // Counter blocks follow modes::ctr, the iv with a big-endian u64 counter in the last 8 bytes
+    fn ctr_batch(&self, records: &[([u8; 16], &[u8])], ranges: &[(usize, usize)], bytes: &mut [u8]) {
+        let mut window = Zeroizing::new([0u8; 16 * LANES]);
+        let mut targets = [(0, 0); LANES]; // where each keystream block goes in "bytes"
+        let mut used = 0;
+
+        let flush = |window: &mut [u8; 16 * LANES], targets: &[(usize, usize)], bytes: &mut [u8]| {
+            self.encrypt_blocks(&mut window[..16 * targets.len()]);
+            for (keystream, &(start, end)) in window.chunks_exact(16).zip(targets) {
+                bytes[start..end].iter_mut().zip(keystream).for_each(|(b, k)| *b ^= k);
+            }
+        };
+
+        for ((iv, _), &(start, end)) in records.iter().zip(ranges) {
+            let counter = u64::from_be_bytes(iv[8..].try_into().expect("8-byte counter"));
+            for (n, at) in (start..end).step_by(16).enumerate() {
+                window[16 * used..16 * used + 8].copy_from_slice(&iv[..8]);
+                window[16 * used + 8..16 * (used + 1)].copy_from_slice(&counter.wrapping_add(n as u64).to_be_bytes());
+                targets[used] = (at, end.min(at + 16));
+                used += 1;
+                if used == LANES {
+                    flush(&mut window, &targets, bytes);
+                    used = 0;
+                }
+            }
+        }
+        flush(&mut window, &targets[..used], bytes);
+    }
+
+    // CBC encrypt up to LANES records side by side, block n of each record in one pipeline call
+    fn cbc_lockstep(&self, records: &[([u8; 16], &[u8])], ranges: &[(usize, usize)], bytes: &mut [u8]) {
+        let mut chains = [[0u8; 16]; LANES];
+        for (chain, (iv, _)) in chains.iter_mut().zip(records) {
+            *chain = *iv;
+        }
+        let mut lanes = Zeroizing::new([0u8; 16 * LANES]);
+        let nblocks = ranges.iter().map(|(start, end)| (end - start) / 16).max().unwrap_or(0);
+
+        for n in 0..nblocks {
+            // Records with a block n, in lane order
+            let active = || {
+                ranges
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, (start, end))| start + 16 * n < *end)
+            };
+
+            let mut used = 0;
+            for (i, (start, _)) in active() {
+                let block = &bytes[start + 16 * n..start + 16 * (n + 1)];
+                for ((l, b), c) in lanes[16 * used..16 * (used + 1)].iter_mut().zip(block).zip(chains[i]) {
+                    *l = b ^ c;
+                }
+                used += 1;
+            }
+
+            self.encrypt_blocks(&mut lanes[..16 * used]);
+
+            for (lane, (i, (start, _))) in lanes.chunks_exact(16).zip(active()) {
+                bytes[start + 16 * n..start + 16 * (n + 1)].copy_from_slice(lane);
+                chains[i].copy_from_slice(lane);
+            }
+        }
+        chains.zeroize();
+    }
+}
@@ -0,0 +1,26 @@
+// A block cipher as seen by the modes of operation in src/modes
+//
+// Blocks are byte slices of BLOCK_SIZE bytes, encrypted and decrypted in place.
+// encrypt_blocks/decrypt_blocks process independent blocks (ECB, the CTR keystream)
+// and can be overridden when a backend handles several blocks at once.
+pub trait BlockCipher {
+    const BLOCK_SIZE: usize;
+
+    fn encrypt_block(&self, block: &mut [u8]);
+
+    fn decrypt_block(&self, block: &mut [u8]);
+
+    // "blocks" must be a multiple of BLOCK_SIZE bytes
+    fn encrypt_blocks(&self, blocks: &mut [u8]) {
+        for block in blocks.chunks_exact_mut(Self::BLOCK_SIZE) {
+            self.encrypt_block(block);
+        }
+    }
+
+    // "blocks" must be a multiple of BLOCK_SIZE bytes
+    fn decrypt_blocks(&self, blocks: &mut [u8]) {
+        for block in blocks.chunks_exact_mut(Self::BLOCK_SIZE) {
+            self.decrypt_block(block);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::error::AesError;
+use crate::key::Key;
+use crate::libaes::KeySchedule;
+use crate::modes::gcm::{self, NONCE_SIZE, TAG_SIZE};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use zeroize::Zeroizing;
+
+// Chunked authenticated encryption, the STREAM construction over AES-GCM
+//
+// The plaintext is cut into CHUNK_SIZE chunks, the last one shorter (empty only when the whole
+// plaintext is) and each is written as ciphertext || tag.  Chunk i is sealed with the nonce
+//
+//   prefix (7 bytes) || i (big-endian u32) || 1 for the last chunk, 0 otherwise
+//
+// and the same associated data, e.g. a file header.  A chunk moved to another index, swapped in
+// from another file (a different prefix) or a stream cut at a chunk boundary (no last flag)
+// fails to authenticate.  Nothing is returned from a chunk before its tag is verified.
+//
+// The prefix must be random (or never reused) for each stream under a key.
+//
+// Every chunk is the same size, so ChunkedReader can seek: only the chunks read are decrypted.
+
+pub const CHUNK_SIZE: usize = 64 * 1024;
+pub const PREFIX_SIZE: usize = 7;
+
+// Ciphertext bytes of a full chunk
+const SEALED_SIZE: u64 = (CHUNK_SIZE + TAG_SIZE) as u64;
+
+fn nonce(prefix: &[u8; PREFIX_SIZE], index: u32, last: bool) -> [u8; NONCE_SIZE] {
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce[..PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[PREFIX_SIZE..NONCE_SIZE - 1].copy_from_slice(&index.to_be_bytes());
+    nonce[NONCE_SIZE - 1] = last as u8;
+    nonce
+}
+
+fn too_long() -> io::Error {
+    io::Error::other("more than 2^32 chunks")
+}
+
+pub struct ChunkedWriter<W: Write> {
+    inner: W,
+    cipher: KeySchedule,
+    prefix: [u8; PREFIX_SIZE],
+    aad: Vec<u8>,
+    index: u32,
+    buf: Zeroizing<Vec<u8>>, // plaintext of the next chunk, never reallocated
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub fn new(inner: W, key: &Key, prefix: &[u8; PREFIX_SIZE], aad: &[u8]) -> Self {
+        Self {
+            inner,
+            cipher: KeySchedule::new(key),
+            prefix: *prefix,
+            aad: aad.to_vec(),
+            index: 0,
+            buf: Zeroizing::new(Vec::with_capacity(CHUNK_SIZE)),
+        }
+    }
+
+    // Seal what's buffered as the last chunk, flush, and return the inner writer
+    //
+    // There is no finish on drop: output without its last chunk fails to decrypt, as it should
+    // when writing stopped early
+    pub fn finish(mut self) -> io::Result<W> {
+        self.seal(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    // Encrypt and write the buffered chunk
+    fn seal(&mut self, last: bool) -> io::Result<()> {
+        let nonce = nonce(&self.prefix, self.index, last);
+        let tag = gcm::encrypt(&self.cipher, &nonce, &self.aad, &mut self.buf);
+        self.inner.write_all(&self.buf)?;
+        self.inner.write_all(&tag)?;
+        self.buf.clear();
+        self.index = self.index.checked_add(1).ok_or_else(too_long)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        // A full chunk is only sealed once more data shows it isn't the last
+        if self.buf.len() == CHUNK_SIZE && !data.is_empty() {
+            self.seal(false)?;
+        }
+        let n = data.len().min(CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub struct ChunkedReader<R: Read> {
+    inner: R,
+    cipher: KeySchedule,
+    prefix: [u8; PREFIX_SIZE],
+    aad: Vec<u8>,
+    index: u32,                // chunk in "plain", or the next one to read when not loaded
+    loaded: bool,              // "plain" holds chunk "index"
+    last: bool,                // ... which is the last chunk
+    plain: Zeroizing<Vec<u8>>, // decrypted chunk
+    pos: usize,                // read position in "plain", or where to start once loaded
+    sealed: Vec<u8>,           // ciphertext of a chunk and a byte read ahead
+    ahead: bool,               // "sealed" starts with a byte of the next chunk
+    body: Option<(u64, u64)>,  // (start, length) of the chunks in "inner", once seeking
+}
+
+impl<R: Read> ChunkedReader<R> {
+    // Reads the chunks from the current position of "inner" to its end
+    pub fn new(inner: R, key: &Key, prefix: &[u8; PREFIX_SIZE], aad: &[u8]) -> Self {
+        Self {
+            inner,
+            cipher: KeySchedule::new(key),
+            prefix: *prefix,
+            aad: aad.to_vec(),
+            index: 0,
+            loaded: false,
+            last: false,
+            plain: Zeroizing::new(Vec::with_capacity(CHUNK_SIZE)),
+            pos: 0,
+            sealed: Vec::with_capacity(CHUNK_SIZE + TAG_SIZE + 1),
+            ahead: false,
+            body: None,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Read and verify chunk "self.index" from the inner reader, which is positioned at it
+    //
+    // Without the length of the input, the chunk is the last when no byte follows it
+    fn load(&mut self) -> io::Result<()> {
+        self.loaded = false;
+        let carried = self.ahead as usize;
+        self.sealed.truncate(carried);
+        self.ahead = false;
+        let want = CHUNK_SIZE + TAG_SIZE + 1 - carried;
+        (&mut self.inner).take(want as u64).read_to_end(&mut self.sealed)?;
+
+        let last = match self.body {
+            Some((_, len)) => self.index as u64 == chunks(len)? - 1,
+            None => self.sealed.len() <= CHUNK_SIZE + TAG_SIZE,
+        };
+        let len = self.sealed.len().min(CHUNK_SIZE + TAG_SIZE);
+        if len < TAG_SIZE {
+            return Err(AesError::InvalidCiphertextLength(len).into());
+        }
+
+        let (ciphertext, tag) = self.sealed[..len].split_at(len - TAG_SIZE);
+        self.plain.clear();
+        self.plain.extend_from_slice(ciphertext);
+        let nonce = nonce(&self.prefix, self.index, last);
+        gcm::decrypt(
+            &self.cipher,
+            &nonce,
+            &self.aad,
+            &mut self.plain,
+            tag.try_into().expect("16-byte tag"),
+        )?;
+
+        // Keep the byte read past this chunk
+        if self.sealed.len() > len {
+            self.sealed.drain(..len);
+            self.ahead = true;
+        }
+        self.loaded = true;
+        self.last = last;
+        Ok(())
+    }
+}
+
+// Chunks in "len" bytes of ciphertext, the last one holding at least a tag
+fn chunks(len: u64) -> io::Result<u64> {
+    let n = len.div_ceil(SEALED_SIZE).max(1);
+    match len.checked_sub((n - 1) * SEALED_SIZE) {
+        Some(rest) if rest >= TAG_SIZE as u64 => Ok(n),
+        _ => Err(AesError::InvalidCiphertextLength((len % SEALED_SIZE) as usize).into()),
+    }
+}
+
+impl<R: Read> Read for ChunkedReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.loaded && self.pos == self.plain.len() && !self.last {
+            self.index = self.index.checked_add(1).ok_or_else(too_long)?;
+            self.loaded = false;
+            self.pos = 0;
+        }
+        if !self.loaded {
+            self.load()?;
+        }
+
+        let start = self.pos.min(self.plain.len());
+        let n = out.len().min(self.plain.len() - start);
+        out[..n].copy_from_slice(&self.plain[start..start + n]);
+        self.pos = start + n;
+        Ok(n)
+    }
+}
+
+// Seeks in the plaintext, the chunk holding the new position is read on the next read()
+impl<R: Read + Seek> Seek for ChunkedReader<R> {
+    fn seek(&mut self, to: SeekFrom) -> io::Result<u64> {
+        // Where the chunks start and how long they are, from what has been read so far
+        let (start, len) = match self.body {
+            Some(body) => body,
+            None => {
+                // Earlier chunks, the loaded one and a byte read ahead
+                let consumed = self.index as u64 * SEALED_SIZE
+                    + match self.loaded {
+                        true => (self.plain.len() + TAG_SIZE) as u64,
+                        false => 0,
+                    }
+                    + self.ahead as u64;
+                let start = self.inner.stream_position()? - consumed;
+                let end = self.inner.seek(SeekFrom::End(0))?;
+                self.body = Some((start, end - start));
+                (start, end - start)
+            }
+        };
+
+        let n = chunks(len)?;
+        let plain_len = len - n * TAG_SIZE as u64;
+        let here = self.index as u64 * CHUNK_SIZE as u64 + self.pos as u64;
+        let target = match to {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => plain_len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => here.checked_add_signed(delta),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start"))?;
+
+        // Past the end reads nothing, from the last chunk
+        let index = (target / CHUNK_SIZE as u64).min(n - 1);
+        self.index = u32::try_from(index).map_err(|_| too_long())?;
+        self.pos = (target - index * CHUNK_SIZE as u64) as usize;
+        self.loaded = false;
+        self.ahead = false;
+        self.inner.seek(SeekFrom::Start(start + index * SEALED_SIZE))?;
+        Ok(target)
+    }
+}
@@ -0,0 +1,164 @@
+use crate::error::AesError;
+use crate::framing::password;
+use crate::general::Encoding;
+use crate::kdf::{Kdf, ARGON2_PARAMS};
+use crate::key::{Key, KeySize};
+use crate::Cipher;
+
+// Self-describing header written before the ciphertext by the CLI's --container
+//
+//   0..6    magic b"AESENC"
+//   6       version, 1
+//   7       mode: 0 ECB, 1 CBC, 2 CTR, 3 AES-GCM in chunks (see chunked.rs)
+//   8       key size in bytes: 16, 24 or 32
+//   9       flags: bit 0 --nopkcs, bits 1-2 output encoding (0 raw, 1 Base-64, 2 Hex)
+//   10      KDF: 0 none (the key is used as is), 1 PBKDF2-HMAC-SHA256, 2 Argon2id
+//   11      reserved, 0
+//   12..24  KDF parameters, big-endian u32s: PBKDF2 iterations, 0, 0 or Argon2 memory (KiB), passes, lanes
+//   24..40  salt, 0 without a KDF
+//   40..56  iv, 0 for ECB, for chunks the nonce prefix followed by 0s
+//   56..64  reserved, 0
+//
+// A KDF only derives the key, the iv is random and stored here.  Decrypting needs nothing but
+// the key or password.  The encoding is a record of how the file was written, readers find the
+// magic in raw, Base-64 or Hex input.  Readers reject versions and values they don't know,
+// nonzero reserved bytes and KDF costs a crafted header could use to stall them: PBKDF2 above
+// MAX_PBKDF2_ITER iterations, Argon2 other than ARGON2_PARAMS.
+//
+// Chunks are authenticated with all 64 header bytes as associated data, ECB, CBC and CTR files
+// aren't authenticated at all.
+
+pub const MAGIC: [u8; 6] = *b"AESENC";
+pub const VERSION: u8 = 1;
+pub const HEADER_LEN: usize = 64;
+pub const MAX_PBKDF2_ITER: u32 = 10_000_000;
+
+// How the data after the header is encrypted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Cipher(Cipher), // padded with PKCS#7 unless --nopkcs
+    Chunked,        // AES-GCM, a tag every chunked::CHUNK_SIZE bytes
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub mode: Mode,
+    pub size: KeySize,
+    pub nopkcs: bool,
+    pub encoding: Option<Encoding>,
+    pub kdf: Option<Kdf>,
+    pub salt: [u8; 16],
+    pub iv: [u8; 16],
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[..6].copy_from_slice(&MAGIC);
+        bytes[6] = VERSION;
+        bytes[7] = match self.mode {
+            Mode::Cipher(Cipher::ECB) => 0,
+            Mode::Cipher(Cipher::CBC) => 1,
+            Mode::Cipher(Cipher::CTR) => 2,
+            Mode::Chunked => 3,
+        };
+        bytes[8] = self.size.bytes() as u8;
+        bytes[9] = self.nopkcs as u8
+            | match self.encoding {
+                None => 0,
+                Some(Encoding::Base64) => 1 << 1,
+                Some(Encoding::Hex) => 2 << 1,
+            };
+
+        let (id, params) = match self.kdf {
+            None => (0, [0; 3]),
+            Some(Kdf::PBKDF2(iter)) => (1, [iter, 0, 0]),
+            Some(Kdf::ARGON2) => (2, ARGON2_PARAMS),
+        };
+        bytes[10] = id;
+        for (field, param) in bytes[12..24].chunks_exact_mut(4).zip(params) {
+            field.copy_from_slice(&param.to_be_bytes());
+        }
+
+        bytes[24..40].copy_from_slice(&self.salt);
+        bytes[40..56].copy_from_slice(&self.iv);
+        bytes
+    }
+
+    // Parse the first HEADER_LEN bytes of "bytes"
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AesError> {
+        let invalid = |what: &str| AesError::Format(format!("container: {what}"));
+
+        if bytes.len() < HEADER_LEN {
+            return Err(AesError::InvalidCiphertextLength(bytes.len()));
+        }
+        if bytes[..6] != MAGIC {
+            return Err(invalid("not a container"));
+        }
+        if bytes[6] != VERSION {
+            return Err(invalid(&format!("unsupported version {}", bytes[6])));
+        }
+        if let Some(i) = (11..12).chain(56..HEADER_LEN).find(|&i| bytes[i] != 0) {
+            return Err(invalid(&format!("reserved byte {i} is not 0")));
+        }
+
+        let mode = match bytes[7] {
+            0 => Mode::Cipher(Cipher::ECB),
+            1 => Mode::Cipher(Cipher::CBC),
+            2 => Mode::Cipher(Cipher::CTR),
+            3 => Mode::Chunked,
+            n => return Err(invalid(&format!("unknown mode {n}"))),
+        };
+        let size = match bytes[8] {
+            16 => KeySize::Aes128,
+            24 => KeySize::Aes192,
+            32 => KeySize::Aes256,
+            n => return Err(invalid(&format!("unknown key size {n}"))),
+        };
+        let encoding = match bytes[9] >> 1 {
+            0 => None,
+            1 => Some(Encoding::Base64),
+            2 => Some(Encoding::Hex),
+            _ => return Err(invalid(&format!("unknown flags {:#04x}", bytes[9]))),
+        };
+
+        let params: [u32; 3] = core::array::from_fn(|i| {
+            u32::from_be_bytes(bytes[12 + 4 * i..16 + 4 * i].try_into().expect("4-byte parameter"))
+        });
+        let kdf = match (bytes[10], params) {
+            (0, _) => None,
+            (1, [iter, 0, 0]) if iter <= MAX_PBKDF2_ITER => Some(Kdf::PBKDF2(iter)),
+            (1, [iter, 0, 0]) => {
+                return Err(AesError::KdfError(format!(
+                    "PBKDF2 {iter} iterations is above {MAX_PBKDF2_ITER}"
+                )))
+            }
+            (1, _) => return Err(invalid("PBKDF2 parameters after the iterations are not 0")),
+            (2, ARGON2_PARAMS) => Some(Kdf::ARGON2),
+            (2, [m, t, p]) => {
+                return Err(AesError::KdfError(format!(
+                    "Argon2 memory {m} KiB, {t} passes, {p} lanes is not supported"
+                )))
+            }
+            (n, _) => return Err(invalid(&format!("unknown KDF {n}"))),
+        };
+
+        Ok(Self {
+            mode,
+            size,
+            nopkcs: bytes[9] & 1 == 1,
+            encoding,
+            kdf,
+            salt: bytes[24..40].try_into().expect("16-byte salt"),
+            iv: bytes[40..56].try_into().expect("16-byte iv"),
+        })
+    }
+
+    // The key to encrypt or decrypt with: derived from "key" and the salt, or "key" itself
+    pub fn key(&self, key: &Key) -> Result<Key, AesError> {
+        match self.kdf {
+            Some(ref hasher) => Ok(hasher.keyiv(self.size, &password(key), &self.salt)?.0),
+            None => Ok(key.clone()),
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::error::AesError;
+use crate::key::Key;
+use crate::libaes::KeySchedule;
+use crate::modes::gcm::{self, NONCE_SIZE, TAG_SIZE};
+use base64::{engine::general_purpose, Engine as _};
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::ser::{self, Serializer};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use zeroize::Zeroizing;
+
+// A field stored encrypted inside a larger serde document (JSON, TOML, ...)
+//
+// The value is serialized to JSON, encrypted with AES-GCM under a random 96-bit nonce and
+// written as the Base-64 string of nonce || ciphertext || tag.  Deserializing verifies the
+// tag before decoding the value, a wrong key or an edited string is an error.
+//
+// The key comes from with_key() on the current thread, or use seal()/open() directly
+//
+//     #[derive(Serialize, Deserialize)]
+//     struct Config {
+//         user: String,
+//         password: Encrypted<String>,
+//     }
+//
+//     let toml = aes::encrypted::with_key(&key, || toml::to_string(&config))?;
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Encrypted<T>(pub T);
+
+thread_local! {
+    static KEY: RefCell<Option<Key>> = const { RefCell::new(None) };
+}
+
+// Run "f" with "key" used by Encrypted<T> serialization on this thread, the previous key
+// (if any) is restored afterwards, also when "f" panics
+pub fn with_key<R>(key: &Key, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Key>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            KEY.with(|k| *k.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(KEY.with(|k| k.borrow_mut().replace(key.clone())));
+    f()
+}
+
+fn current_key<R>(f: impl FnOnce(&Key) -> R) -> Option<R> {
+    KEY.with(|k| k.borrow().as_ref().map(f))
+}
+
+impl<T> Encrypted<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: Serialize> Encrypted<T> {
+    // The Base-64 string Serialize writes
+    pub fn seal(&self, key: &Key) -> Result<String, AesError> {
+        let mut sealed =
+            Zeroizing::new(serde_json::to_vec(&self.0).map_err(|e| AesError::InvalidArgument(e.to_string()))?);
+
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+        let tag = gcm::encrypt(&KeySchedule::new(key), &nonce, &[], &mut sealed);
+
+        let mut out = Vec::with_capacity(NONCE_SIZE + sealed.len() + TAG_SIZE);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&sealed);
+        out.extend_from_slice(&tag);
+        Ok(general_purpose::STANDARD.encode(out))
+    }
+}
+
+impl<T: DeserializeOwned> Encrypted<T> {
+    // Verify and decrypt a string written by seal() or Serialize
+    pub fn open(key: &Key, sealed: &str) -> Result<Self, AesError> {
+        let bytes = general_purpose::STANDARD
+            .decode(sealed)
+            .map_err(|e| AesError::Decode(format!("Base-64: {e}")))?;
+        if bytes.len() < NONCE_SIZE + TAG_SIZE {
+            return Err(AesError::InvalidCiphertextLength(bytes.len()));
+        }
+
+        let (nonce, rest) = bytes.split_at(NONCE_SIZE);
+        let (ciphertext, tag) = rest.split_at(rest.len() - TAG_SIZE);
+        let mut json = Zeroizing::new(ciphertext.to_vec());
+        gcm::decrypt(
+            &KeySchedule::new(key),
+            nonce.try_into().expect("12-byte nonce"),
+            &[],
+            &mut json,
+            tag.try_into().expect("16-byte tag"),
+        )?;
+
+        serde_json::from_slice(&json)
+            .map(Self)
+            .map_err(|e| AesError::Decode(format!("JSON: {e}")))
+    }
+}
+
+impl<T: Serialize> Serialize for Encrypted<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sealed = current_key(|key| self.seal(key))
+            .ok_or_else(|| ser::Error::custom("Encrypted<T> needs a key, see aes::encrypted::with_key"))?
+            .map_err(ser::Error::custom)?;
+        serializer.serialize_str(&sealed)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Encrypted<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let sealed = String::deserialize(deserializer)?;
+        current_key(|key| Self::open(key, &sealed))
+            .ok_or_else(|| de::Error::custom("Encrypted<T> needs a key, see aes::encrypted::with_key"))?
+            .map_err(de::Error::custom)
+    }
+}
+
+impl<T> Deref for Encrypted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Encrypted<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+// Never shows the value
+impl<T> fmt::Debug for Encrypted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Encrypted(..)")
+    }
+}
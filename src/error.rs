@@ -0,0 +1,101 @@
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+use core::fmt;
+
+// Errors returned by the library, each maps to a distinct exit code in the CLI
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum AesError {
+    InvalidKeyLength(usize), // bits not in [128, 192, 256]
+    KeySizeMismatch {
+        expected: usize,
+        bits: usize,
+    }, // key bits other than those of the KeySize asked for
+    InvalidCiphertextLength(usize), // ECB/CBC ciphertext not a multiple of 16, or a short 1st block
+    BufferTooSmall {
+        needed: usize,
+        len: usize,
+    }, // no room for the padded ciphertext
+    BadPadding,              // PKCS#7 padding check failed, usually a wrong key
+    AuthenticationFailed,    // authentication tag mismatch
+    EmptyInput,
+    #[cfg(feature = "alloc")]
+    KdfError(String),
+    #[cfg(feature = "alloc")]
+    Decode(String), // Base-64/Hex input, key or iv
+    #[cfg(feature = "alloc")]
+    InvalidArgument(String),
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    #[cfg(feature = "alloc")]
+    Format(String), // malformed or unsupported file header
+}
+
+impl fmt::Display for AesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidKeyLength(bits) => write!(f, "{bits} not in [128, 192, 256]"),
+            Self::KeySizeMismatch { expected, bits } => write!(f, "{bits}-bit key, expected {expected}"),
+            Self::InvalidCiphertextLength(n) => {
+                write!(f, "{n} bytes of ciphertext is not a multiple of the block size")
+            }
+            Self::BufferTooSmall { needed, len } => {
+                write!(f, "{len} byte buffer has no room for {needed} bytes of ciphertext")
+            }
+            Self::BadPadding => write!(f, "bad PKCS#7 padding (wrong key?)"),
+            Self::AuthenticationFailed => write!(f, "authentication failed (wrong key or modified ciphertext)"),
+            Self::EmptyInput => write!(f, "empty input"),
+            #[cfg(feature = "alloc")]
+            Self::KdfError(e) => write!(f, "key derivation: {e}"),
+            #[cfg(feature = "alloc")]
+            Self::Decode(e) => write!(f, "decode: {e}"),
+            #[cfg(feature = "alloc")]
+            Self::InvalidArgument(e) => write!(f, "{e}"),
+            #[cfg(feature = "std")]
+            Self::Io(e) => write!(f, "{e}"),
+            #[cfg(feature = "alloc")]
+            Self::Format(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl core::error::Error for AesError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for AesError {
+    fn from(e: std::io::Error) -> Self {
+        // An AesError that passed through a Read/Write adapter comes back as itself
+        match e.get_ref().is_some_and(|inner| inner.is::<AesError>()) {
+            true => *e
+                .into_inner()
+                .and_then(|inner| inner.downcast().ok())
+                .expect("checked above"),
+            false => Self::Io(e),
+        }
+    }
+}
+
+// For the Read/Write adapters: padding and length errors are bad data
+#[cfg(feature = "std")]
+impl From<AesError> for std::io::Error {
+    fn from(e: AesError) -> Self {
+        use std::io::{Error, ErrorKind};
+        match e {
+            AesError::Io(e) => e,
+            AesError::InvalidCiphertextLength(_)
+            | AesError::BadPadding
+            | AesError::AuthenticationFailed
+            | AesError::Decode(_)
+            | AesError::Format(_) => Error::new(ErrorKind::InvalidData, e),
+            e => Error::other(e),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::error::AesError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Suffix added on encrypt and removed on decrypt
+const SUFFIX: &str = "aes";
+
+// An input file and the file to write, or why it can't be processed
+pub type Job = (PathBuf, Result<PathBuf, AesError>);
+
+// Expand "paths" into input files, directories only with "recursive", and name each output
+//
+// Encrypting writes name.aes next to the input, decrypting strips the .aes (a name without one is
+// only kept below "out_dir"), "in_place" replaces the input.  With "out_dir" outputs mirror the inputs below it: for the path a/b.txt it's
+// out_dir/b.txt.aes, for b/c.txt found in the directory a/ it's out_dir/a/b/c.txt.aes.
+//
+// Directories are walked in name order without following symlinked directories.  Files found
+// there that already end in .aes are skipped when encrypting, only those are taken when decrypting.
+// In place every file is taken, names don't change so they tell nothing.
+pub fn plan(paths: &[PathBuf], encrypt: bool, recursive: bool, out_dir: Option<&PathBuf>, in_place: bool) -> Vec<Job> {
+    let wanted = |path: &Path| in_place || has_suffix(path) != encrypt;
+    let mut jobs = vec![];
+    for path in paths {
+        if path.as_os_str() == "-" {
+            let e = AesError::InvalidArgument("standard input needs a single FILE".into());
+            jobs.push((path.clone(), Err(e)));
+            continue;
+        }
+
+        // Outputs below "out_dir" keep the path from here on, the directory name of a/ or
+        // the contents of . and ..
+        let base = match path.file_name() {
+            Some(_) => path.parent().unwrap_or(Path::new("")),
+            None => path,
+        };
+
+        let mut inputs = vec![];
+        match fs::metadata(path).map(|meta| meta.is_dir()) {
+            Ok(true) if recursive => walk(path, &wanted, &mut inputs),
+            Ok(true) => inputs.push((path.clone(), Err(dir_error(path)))),
+            _ => inputs.push((path.clone(), Ok(()))),
+        }
+
+        for (input, found) in inputs {
+            let output = found.and_then(|()| output(&input, base, encrypt, out_dir, in_place));
+            jobs.push((input, output));
+        }
+    }
+    jobs
+}
+
+fn dir_error(path: &Path) -> AesError {
+    AesError::InvalidArgument(format!("`{:?}` is a directory, use -R", path.as_os_str()))
+}
+
+fn has_suffix(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == SUFFIX)
+}
+
+// Files below "dir" that are "wanted"
+fn walk(dir: &Path, wanted: &dyn Fn(&Path) -> bool, inputs: &mut Vec<(PathBuf, Result<(), AesError>)>) {
+    let mut entries = match fs::read_dir(dir).and_then(|entries| entries.collect::<Result<Vec<_>, _>>()) {
+        Ok(entries) => entries,
+        Err(e) => return inputs.push((dir.to_path_buf(), Err(e.into()))),
+    };
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => walk(&path, wanted, inputs),
+            // Symlinks to files are read, symlinks to directories skipped
+            Ok(kind) if kind.is_symlink() && path.is_dir() => (),
+            Ok(_) if wanted(&path) => inputs.push((path, Ok(()))),
+            Ok(_) => (),
+            Err(e) => inputs.push((path, Err(e.into()))),
+        }
+    }
+}
+
+// The file written for "input"
+fn output(
+    input: &Path,
+    base: &Path,
+    encrypt: bool,
+    out_dir: Option<&PathBuf>,
+    in_place: bool,
+) -> Result<PathBuf, AesError> {
+    if in_place {
+        return Ok(input.canonicalize()?);
+    }
+
+    let name = match encrypt {
+        true => {
+            let mut name = input.as_os_str().to_owned();
+            name.push(".");
+            name.push(SUFFIX);
+            PathBuf::from(name)
+        }
+        false => match has_suffix(input) {
+            true => input.with_extension(""),
+            // Below "out_dir" the name can stay
+            false if out_dir.is_some() => input.to_path_buf(),
+            false => {
+                return Err(AesError::InvalidArgument(format!(
+                    "`{:?}` doesn't end in .{SUFFIX}, use --out-dir or --in-place",
+                    input.as_os_str()
+                )))
+            }
+        },
+    };
+
+    Ok(match out_dir {
+        Some(dir) => dir.join(name.strip_prefix(base).unwrap_or(&name)),
+        None => name,
+    })
+}
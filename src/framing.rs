@@ -0,0 +1,95 @@
+use crate::error::AesError;
+use crate::kdf::Kdf;
+use crate::key::Key;
+use crate::secret::SecretBuf;
+use crate::Cipher;
+
+// Layout of the 1st block of ciphertext, as written and read by the CLI
+//
+//   --pbkdf2              b"Salted__" followed by the 8-byte salt (OpenSSL compatible)
+//   --argon2              16-byte salt
+//   --randiv [CBC, CTR]   16-byte iv
+//   otherwise             no 1st block, the iv is supplied
+//
+// With a KDF the supplied key is stretched with the salt into a key of the same size
+// and the iv actually used, without one the key is used as is
+
+// The (key, iv) to encrypt or decrypt with
+pub type KeyIv = (Key, [u8; 16]);
+
+#[derive(Clone, Debug)]
+pub struct Framing {
+    pub cipher: Cipher,
+    pub kdf: Option<Kdf>,
+    pub randiv: bool,
+    pub nopkcs: bool, // prevent a full pad block on encrypt, skip PKCS#7 pad removal on decrypt
+}
+
+impl Framing {
+    // Number of bytes in the 1st block: 0 or 16
+    pub fn header_len(&self) -> usize {
+        match self.kdf.is_some() || (self.randiv && self.cipher != Cipher::ECB) {
+            true => 16,
+            false => 0,
+        }
+    }
+
+    // Returns the 1st block of output and the (key, iv) to encrypt with
+    //
+    // "ivector" is the supplied iv, or salt when using a KDF, and should be random
+    // when the 1st block carries it
+    pub fn encrypt_header(&self, key: &Key, ivector: &[u8; 16]) -> Result<(Vec<u8>, KeyIv), AesError> {
+        let mut header = vec![];
+        let (key, ivector) = match self.kdf {
+            Some(ref hasher) => match hasher {
+                Kdf::PBKDF2(_) => {
+                    // b"Salted__xxxxxxxx"
+                    header.extend(b"Salted__");
+                    header.extend(&ivector[8..]);
+                    hasher.keyiv(key.size(), &password(key), &ivector[8..])?
+                }
+                Kdf::ARGON2 => {
+                    header.extend(ivector);
+                    hasher.keyiv(key.size(), &password(key), ivector)?
+                }
+            },
+            None => {
+                if self.header_len() > 0 {
+                    header.extend(ivector);
+                }
+                (key.clone(), *ivector)
+            }
+        };
+        Ok((header, (key, ivector)))
+    }
+
+    // Returns the (key, iv) to decrypt with given the 1st block of input
+    //
+    // "block" must hold header_len() bytes, "ivector" is the supplied iv used when it is empty
+    pub fn decrypt_header(&self, key: &Key, ivector: &[u8; 16], block: &[u8]) -> Result<KeyIv, AesError> {
+        if block.len() != self.header_len() {
+            return Err(AesError::InvalidCiphertextLength(block.len()));
+        }
+
+        let mut ivector = *ivector;
+        if !block.is_empty() {
+            ivector.copy_from_slice(block);
+        }
+
+        Ok(match self.kdf {
+            Some(ref hasher) => match hasher {
+                // ivector contains the salt, skip over b"Salted__"
+                Kdf::PBKDF2(_) => hasher.keyiv(key.size(), &password(key), &ivector[8..])?,
+                Kdf::ARGON2 => hasher.keyiv(key.size(), &password(key), &ivector)?,
+            },
+            None => (key.clone(), ivector),
+        })
+    }
+}
+
+// The KDF password has always been the key zero-padded to 32 bytes
+pub(crate) fn password(key: &Key) -> SecretBuf {
+    let mut password = SecretBuf::new(32);
+    password[..key.as_bytes().len()].copy_from_slice(key.as_bytes());
+    password
+}
@@ -1,69 +1,567 @@
-use anyhow::{Context, Result};
+use crate::error::AesError;
+use crate::key::{Key, KeySize};
+use crate::secret::SecretBuf;
 use base64::{engine::general_purpose, Engine as _};
-use std::error::Error;
-use std::fs::File;
-use std::io::{self, Read};
-use std::path::PathBuf;
+use memmap2::Mmap;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use zeroize::{Zeroize, Zeroizing};
 
 const BASE64_ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=";
 const HEX_ALPHABET: &str = "ABCDEFabcdef0123456789";
 
+// Size of the pieces handed out by Input::next_chunk()
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+// Prefix an io::Error with what was being attempted
+fn context(msg: String) -> impl FnOnce(io::Error) -> AesError {
+    move |e| AesError::Io(io::Error::new(e.kind(), format!("{msg}: {e}")))
+}
+
 // https://github.com/rust-lang/rust/issues/62569
-pub fn reset_sigpipe() -> Result<(), Box<dyn Error>> {
+pub fn reset_sigpipe() -> Result<(), AesError> {
     #[cfg(target_family = "unix")]
     {
         use nix::sys::signal;
 
         unsafe {
-            signal::signal(signal::Signal::SIGPIPE, signal::SigHandler::SigDfl)?;
+            signal::signal(signal::Signal::SIGPIPE, signal::SigHandler::SigDfl)
+                .map_err(|e| io::Error::from_raw_os_error(e as i32))?;
         }
     }
     Ok(())
 }
 
-// Read bytes from a file or stdin and decode from Base-64 or 2-byte Hex
-pub fn read_input_bytes(file: Option<&PathBuf>, b64: bool, hex: bool) -> Result<Vec<u8>, Box<dyn Error>> {
-    let file = match file {
-        Some(file) => file.clone(),
-        _ => std::path::PathBuf::from("-"),
-    };
+// Keep key material out of core files: not dumpable (Linux) and a zero core size limit
+pub fn disable_core_dumps() -> Result<(), AesError> {
+    #[cfg(target_os = "linux")]
+    nix::sys::prctl::set_dumpable(false).map_err(|e| io::Error::from_raw_os_error(e as i32))?;
 
-    let mut bytes = vec![];
-    let _input_name: String = match file.as_os_str() != "-" {
-        true => {
-            File::open(&file)
-                .with_context(|| format!("could not open file `{:?}`", file.as_os_str()))?
-                .read_to_end(&mut bytes)
-                .with_context(|| format!("could not read file `{:?}`", file.as_os_str()))?;
-            file.to_string_lossy().into()
-        }
-        false => {
-            io::stdin()
-                .read_to_end(&mut bytes)
-                .with_context(|| "could not read `stdin`")?;
-            "<stdin>".into()
+    #[cfg(target_family = "unix")]
+    {
+        use nix::sys::resource::{setrlimit, Resource};
+
+        setrlimit(Resource::RLIMIT_CORE, 0, 0).map_err(|e| io::Error::from_raw_os_error(e as i32))?;
+    }
+    Ok(())
+}
+
+// Text encodings of the CLI's input and output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Base64,
+    Hex,
+}
+
+impl Encoding {
+    fn alphabet(self) -> &'static str {
+        match self {
+            Self::Base64 => BASE64_ALPHABET,
+            Self::Hex => HEX_ALPHABET,
+        }
+    }
+
+    // Characters decoded as a unit
+    fn group(self) -> usize {
+        match self {
+            Self::Base64 => 4,
+            Self::Hex => 2,
+        }
+    }
+}
+
+// Base-64 or 2-byte Hex decoded as it's read, characters outside the alphabet (newlines) are skipped
+//
+// Each read() of the inner reader is decoded as far as whole groups allow, so decoded bytes are
+// available as soon as their characters arrive.  Errors are AesError::Decode inside io::Error
+pub struct DecodingReader<R> {
+    inner: R,
+    encoding: Encoding,
+    chunk: Zeroizing<Vec<u8>>,   // raw bytes of one inner read
+    pending: Zeroizing<Vec<u8>>, // alphabet characters not yet decoded
+    decoded: Zeroizing<Vec<u8>>,
+    pos: usize, // bytes of "decoded" handed out
+    eof: bool,
+}
+
+impl<R: Read> DecodingReader<R> {
+    pub fn new(inner: R, encoding: Encoding) -> Self {
+        Self {
+            inner,
+            encoding,
+            chunk: Zeroizing::new(vec![0; CHUNK_SIZE]),
+            pending: Zeroizing::new(vec![]),
+            decoded: Zeroizing::new(vec![]),
+            pos: 0,
+            eof: false,
+        }
+    }
+
+    // Read once from the inner reader and decode every complete group
+    fn fill(&mut self) -> io::Result<()> {
+        let n = loop {
+            match self.inner.read(&mut self.chunk) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+        self.eof = n == 0;
+
+        let alphabet = self.encoding.alphabet();
+        self.pending
+            .extend(self.chunk[..n].iter().filter(|&&b| alphabet.contains(char::from(b))));
+        self.chunk[..n].zeroize();
+
+        // Everything at the end of input, otherwise whole groups before any Base-64 padding
+        let mut len = match self.eof {
+            true => self.pending.len(),
+            false => self.pending.len() - self.pending.len() % self.encoding.group(),
+        };
+        if let Some(p) = self.pending[..len].iter().position(|&b| b == b'=') {
+            if !self.eof {
+                len = p - p % 4;
+            }
+        }
+
+        // Only the bytes in use, the spare capacity was wiped when it was last used
+        self.decoded[..].zeroize();
+        self.decoded.clear();
+        self.pos = 0;
+        match self.encoding {
+            Encoding::Base64 => general_purpose::STANDARD
+                .decode_vec(&self.pending[..len], &mut self.decoded)
+                .map_err(|e| AesError::Decode(format!("base64: {e}")))?,
+            Encoding::Hex => {
+                self.decoded.resize(len / 2, 0);
+                hex::decode_to_slice(&self.pending[..len], &mut self.decoded)
+                    .map_err(|e| AesError::Decode(format!("hex: {e}")))?
+            }
+        }
+        self.pending.drain(..len).for_each(drop);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.decoded.len() && !self.eof {
+            self.fill()?;
+        }
+        let n = buf.len().min(self.decoded.len() - self.pos);
+        buf[..n].copy_from_slice(&self.decoded[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+// Base-64 or 2-byte Hex written as the bytes arrive, in lines of 76 characters
+//
+// finish() encodes what's left (Base-64 padding) and ends the last line
+pub struct EncodingWriter<W: Write> {
+    inner: W,
+    encoding: Encoding,
+    pending: Zeroizing<Vec<u8>>, // bytes short of a whole Base-64 group
+    line: Zeroizing<Vec<u8>>,
+    column: usize,
+}
+
+impl<W: Write> EncodingWriter<W> {
+    pub fn new(inner: W, encoding: Encoding) -> Self {
+        Self {
+            inner,
+            encoding,
+            pending: Zeroizing::new(vec![]),
+            line: Zeroizing::new(vec![]),
+            column: 0,
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        let pending = std::mem::take(&mut self.pending);
+        self.emit(&pending)?;
+        if self.column > 0 {
+            self.inner.write_all(b"\n")?;
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    // Encode "bytes" and write the characters wrapped at 76 columns
+    fn emit(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let encoded = Zeroizing::new(match self.encoding {
+            Encoding::Base64 => general_purpose::STANDARD.encode(bytes),
+            Encoding::Hex => hex::encode(bytes),
+        });
+
+        self.line.clear();
+        for &c in encoded.as_bytes() {
+            self.line.push(c);
+            self.column += 1;
+            if self.column == 76 {
+                self.line.push(b'\n');
+                self.column = 0;
+            }
+        }
+        self.inner.write_all(&self.line)
+    }
+}
+
+impl<W: Write> Write for EncodingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Base-64 encodes whole groups of 3 bytes until finish()
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(buf);
+        let len = match self.encoding {
+            Encoding::Base64 => bytes.len() - bytes.len() % 3,
+            Encoding::Hex => bytes.len(),
+        };
+        self.pending.extend_from_slice(&bytes[len..]);
+        self.emit(&bytes[..len])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Input bytes processed a chunk at a time so memory use doesn't grow with the input size
+//
+// Regular files are memory mapped, stdin and other files (pipes, devices) are read in
+// CHUNK_SIZE pieces.  Base-64/Hex input is decoded as it's read, see DecodingReader.
+pub struct Input {
+    source: Source,
+    buf: Vec<u8>, // Source::Reader: bytes read but not yet handed out
+    pos: usize,
+}
+
+enum Source {
+    Mmap(Mmap),
+    Reader(Box<dyn Read>),
+}
+
+// Plaintext passes through "buf" when encrypting
+impl Drop for Input {
+    fn drop(&mut self) {
+        self.buf.zeroize();
+    }
+}
+
+// The file, or stdin for None and "-"
+fn open_reader(file: Option<&PathBuf>) -> Result<Box<dyn Read>, AesError> {
+    match file {
+        Some(file) if file.as_os_str() != "-" => Ok(Box::new(
+            File::open(file).map_err(context(format!("could not open file `{:?}`", file.as_os_str())))?,
+        )),
+        _ => Ok(Box::new(io::stdin())),
+    }
+}
+
+// (b64, hex) of a form of the magic
+type Form = (bool, bool);
+
+// Read the start of "reader" until it is "magic", raw or as Base-64/Hex text, or can't be
+//
+// Returns the bytes read and (b64, hex) of the form found.  Reading stops as soon as the bytes
+// differ from every form, so input in a pipeline isn't held back waiting for more
+fn sniff(reader: &mut dyn Read, magic: &[u8]) -> io::Result<(Vec<u8>, Option<Form>)> {
+    let forms = [
+        (magic.to_vec(), (false, false)),
+        (general_purpose::STANDARD.encode(magic).into_bytes(), (true, false)),
+        (hex::encode(magic).into_bytes(), (false, true)),
+    ];
+    // Do the bytes so far agree with "form", Hex in either case
+    let agrees = |got: &[u8], form: &[u8], hex: bool| {
+        let n = got.len().min(form.len());
+        match hex {
+            true => got[..n].eq_ignore_ascii_case(&form[..n]),
+            false => got[..n] == form[..n],
         }
     };
 
-    // Perform decodings (Base-64, 2-byte Hex)
-    if b64 {
-        // Keep only the Base-64 alphabet and decode
-        bytes.retain(|&b| BASE64_ALPHABET.contains(char::from(b)));
-        bytes = general_purpose::STANDARD
-            .decode(bytes)
-            .with_context(|| "base64::decode")?;
-    } else if hex {
-        // Keep only the Hex alphabet and decode
-        bytes.retain(|&b| HEX_ALPHABET.contains(char::from(b)));
-        bytes = hex::decode(bytes).with_context(|| "hex::decode")?;
+    let mut prefix = vec![0u8; forms.iter().map(|(form, _)| form.len()).max().unwrap_or(0)];
+    let mut n = 0;
+    loop {
+        let got = &prefix[..n];
+        if let Some((_, found)) = forms
+            .iter()
+            .find(|(form, (_, hex))| got.len() >= form.len() && agrees(got, form, *hex))
+        {
+            let found = Some(*found);
+            prefix.truncate(n);
+            return Ok((prefix, found));
+        }
+        if n == prefix.len() || !forms.iter().any(|(form, (_, hex))| agrees(got, form, *hex)) {
+            break;
+        }
+        match reader.read(&mut prefix[n..]) {
+            Ok(0) => break,
+            Ok(len) => n += len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    prefix.truncate(n);
+    Ok((prefix, None))
+}
+
+impl Input {
+    pub fn open(file: Option<&PathBuf>, b64: bool, hex: bool) -> Result<Self, AesError> {
+        let source = match file {
+            _ if b64 => Source::Reader(Box::new(DecodingReader::new(open_reader(file)?, Encoding::Base64))),
+            _ if hex => Source::Reader(Box::new(DecodingReader::new(open_reader(file)?, Encoding::Hex))),
+            Some(file) if file.as_os_str() != "-" => {
+                let f = File::open(file).map_err(context(format!("could not open file `{:?}`", file.as_os_str())))?;
+                let meta = f
+                    .metadata()
+                    .map_err(context(format!("could not read file `{:?}`", file.as_os_str())))?;
+                match meta.is_file() && meta.len() > 0 {
+                    // Safety: the map is read-only, the file must not be truncated while in use
+                    true => {
+                        let map = unsafe { Mmap::map(&f) }
+                            .map_err(context(format!("could not map file `{:?}`", file.as_os_str())))?;
+                        #[cfg(target_family = "unix")]
+                        let _ = map.advise(memmap2::Advice::Sequential);
+                        Source::Mmap(map)
+                    }
+                    false => Source::Reader(Box::new(f)),
+                }
+            }
+            _ => Source::Reader(Box::new(io::stdin())),
+        };
+
+        Ok(Self {
+            source,
+            buf: vec![],
+            pos: 0,
+        })
+    }
+
+    // Like open(), noticing input that starts with "magic" either raw or as Base-64/Hex text
+    //
+    // Returns whether it does, the input is then decoded as found and "b64"/"hex" don't apply.
+    // The magic's length should be a multiple of 3 so its Base-64 form is fixed.
+    pub fn open_detect(file: Option<&PathBuf>, b64: bool, hex: bool, magic: &[u8]) -> Result<(Self, bool), AesError> {
+        let mut reader = open_reader(file)?;
+        match file {
+            // A regular file is opened again from the start, to be mapped.  Anything else (a FIFO,
+            // /dev/stdin) can't be read twice and continues after the bytes sniffed
+            Some(file) if file.as_os_str() != "-" && fs::metadata(file).is_ok_and(|m| m.is_file()) => {
+                let (_, found) = sniff(&mut reader, magic)?;
+                let (b64, hex) = found.unwrap_or((b64, hex));
+                Ok((Self::open(Some(file), b64, hex)?, found.is_some()))
+            }
+            _ => Ok(Self::detect(reader, b64, hex, magic)?),
+        }
+    }
+
+    // open_detect() of a stream, which continues after the bytes sniffed
+    pub(crate) fn detect(mut reader: Box<dyn Read>, b64: bool, hex: bool, magic: &[u8]) -> io::Result<(Self, bool)> {
+        let (prefix, found) = sniff(&mut reader, magic)?;
+        let reader: Box<dyn Read> = Box::new(io::Cursor::new(prefix).chain(reader));
+        let source = match found.unwrap_or((b64, hex)) {
+            (true, _) => Source::Reader(Box::new(DecodingReader::new(reader, Encoding::Base64))),
+            (_, true) => Source::Reader(Box::new(DecodingReader::new(reader, Encoding::Hex))),
+            _ => Source::Reader(reader),
+        };
+        let input = Self {
+            source,
+            buf: vec![],
+            pos: 0,
+        };
+        Ok((input, found.is_some()))
+    }
+
+    // Is there no more input?
+    pub fn is_empty(&mut self) -> io::Result<bool> {
+        Ok(self.peek()?.is_empty())
+    }
+
+    // Up to CHUNK_SIZE bytes, empty at the end of input
+    pub fn next_chunk(&mut self) -> io::Result<&[u8]> {
+        self.take(CHUNK_SIZE)
+    }
+
+    // The next "n" bytes, fewer at the end of input
+    pub fn read_up_to(&mut self, n: usize) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        while bytes.len() < n {
+            let chunk = self.take(n - bytes.len())?;
+            if chunk.is_empty() {
+                break;
+            }
+            bytes.extend_from_slice(chunk);
+        }
+        Ok(bytes)
+    }
+
+    // Up to "n" of the unconsumed bytes
+    fn take(&mut self, n: usize) -> io::Result<&[u8]> {
+        let len = self.peek()?.len().min(n);
+        let start = self.pos;
+        self.pos += len;
+        Ok(match self.source {
+            Source::Mmap(ref map) => &map[start..start + len],
+            Source::Reader(_) => &self.buf[start..start + len],
+        })
+    }
+
+    // The unconsumed bytes, reading more when the buffer of a Reader is exhausted
+    fn peek(&mut self) -> io::Result<&[u8]> {
+        if let Source::Reader(ref mut reader) = self.source {
+            if self.pos == self.buf.len() {
+                self.buf.resize(CHUNK_SIZE, 0);
+                let n = loop {
+                    match reader.read(&mut self.buf) {
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        result => break result?,
+                    }
+                };
+                self.buf.truncate(n);
+                self.pos = 0;
+            }
+        }
+
+        Ok(match self.source {
+            Source::Mmap(ref map) => &map[self.pos..],
+            Source::Reader(_) => &self.buf[self.pos..],
+        })
+    }
+}
+
+// The unconsumed bytes, for readers layered on top such as ChunkedReader
+impl Read for Input {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let chunk = self.take(out.len())?;
+        out[..chunk.len()].copy_from_slice(chunk);
+        Ok(chunk.len())
+    }
+}
+
+// Output file written to a temp file in the same directory and renamed into place by commit()
+//
+// Nothing appears at "path" until commit(), a temp file that wasn't committed is removed on drop.
+// When "path" exists its permissions are copied to the temp file before anything is written.
+pub struct AtomicFile {
+    file: File,
+    tmp: PathBuf,
+    path: PathBuf,
+    force: bool,
+    committed: bool,
+}
+
+impl AtomicFile {
+    // An existing "path" is an error unless "force"
+    pub fn create(path: &Path, force: bool) -> Result<Self, AesError> {
+        let meta = fs::metadata(path).ok();
+        if meta.is_some() && !force {
+            return Err(already_exists(path));
+        }
+
+        let name = path
+            .file_name()
+            .ok_or_else(|| AesError::InvalidArgument(format!("`{:?}` is not a file name", path.as_os_str())))?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        // A hidden name unique in "dir"
+        let (file, tmp) = loop {
+            let mut tmp_name = OsString::from(".");
+            tmp_name.push(name);
+            tmp_name.push(format!(".{:08x}.tmp", rand::random::<u32>()));
+            let tmp = dir.join(tmp_name);
+            match File::options().write(true).create_new(true).open(&tmp) {
+                Ok(file) => break (file, tmp),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(context(format!("could not create a file in `{:?}`", dir.as_os_str()))(
+                        e,
+                    ))
+                }
+            }
+        };
+
+        let atomic = Self {
+            file,
+            tmp,
+            path: path.to_path_buf(),
+            force,
+            committed: false,
+        };
+        if let Some(meta) = meta {
+            atomic
+                .file
+                .set_permissions(meta.permissions())
+                .map_err(context(format!(
+                    "could not set permissions of `{:?}`",
+                    atomic.tmp.as_os_str()
+                )))?;
+        }
+        Ok(atomic)
     }
 
-    Ok(bytes)
+    // Sync the temp file and move it to "path"
+    //
+    // Without "force" a hard link is made so a file created at "path" in the meantime isn't replaced
+    pub fn commit(mut self) -> Result<(), AesError> {
+        let failed = |path: &Path| context(format!("could not write `{:?}`", path.as_os_str()));
+        self.file.sync_all().map_err(failed(&self.tmp))?;
+
+        match self.force {
+            true => fs::rename(&self.tmp, &self.path).map_err(failed(&self.path))?,
+            false => match fs::hard_link(&self.tmp, &self.path) {
+                Ok(()) => fs::remove_file(&self.tmp).map_err(failed(&self.tmp))?,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(already_exists(&self.path)),
+                // No hard links on this file system
+                Err(_) => match self.path.exists() {
+                    true => return Err(already_exists(&self.path)),
+                    false => fs::rename(&self.tmp, &self.path).map_err(failed(&self.path))?,
+                },
+            },
+        }
+        self.committed = true;
+
+        // The rename itself is durable once the directory is synced
+        #[cfg(target_family = "unix")]
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(dir).and_then(|dir| dir.sync_all()).ok();
+        }
+        Ok(())
+    }
+}
+
+fn already_exists(path: &Path) -> AesError {
+    AesError::Io(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("`{:?}` exists, use --force to overwrite", path.as_os_str()),
+    ))
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            fs::remove_file(&self.tmp).ok();
+        }
+    }
 }
 
 // 16-byte initialization vector (random, or bytes from 2-byte hex)
 // Warn on short/long conversions
-pub fn get_ivector(random: bool, iv: Option<&String>, quiet: bool) -> Result<[u8; 16], Box<dyn Error>> {
+pub fn get_ivector(random: bool, iv: Option<&String>, quiet: bool) -> Result<[u8; 16], AesError> {
     let mut ivector = [0u8; 16];
 
     if let Some(hexiv) = iv {
@@ -76,7 +574,7 @@ pub fn get_ivector(random: bool, iv: Option<&String>, quiet: bool) -> Result<[u8
             0 => hexiv.to_string(),
             _ => hexiv.to_owned() + "0",
         };
-        let iv = hex::decode(hexiv).with_context(|| "hex::decode(hexiv)")?;
+        let iv = hex::decode(hexiv).map_err(|e| AesError::Decode(format!("hex iv: {e}")))?;
         let nbytes = 16.min(iv.len());
         ivector[..nbytes].copy_from_slice(&iv[..nbytes]);
     } else if random {
@@ -86,56 +584,60 @@ pub fn get_ivector(random: bool, iv: Option<&String>, quiet: bool) -> Result<[u8
     Ok(ivector)
 }
 
-// Returns number of encryption bits and a 32-byte passkey
-// Bits can be explicitly specified or will be derived from key length
+// Returns the key for the command line --key/--hexkey
+// The size can be explicitly specified or will be derived from key length
 // Key can be raw bytes, or converted from 2-byte hex
-pub fn get_passkey32(
-    bits: Option<usize>,
+//
+// Short keys are padded with zero bytes and long keys truncated to the size, with a warning
+pub fn get_key(
+    size: Option<KeySize>,
     key: Option<&String>,
     hexkey: Option<&String>,
     quiet: bool, // squelch warnings re short/truncated keys?
-) -> Result<(usize, [u8; 32]), Box<dyn Error>> {
+) -> Result<Key, AesError> {
+    // Copies of the key are locked in memory and wiped on drop
     let (keylen, key) = if let Some(key) = key {
-        (key.len(), key.clone().into_bytes())
+        (key.len(), SecretBuf::from_slice(key.as_bytes()))
     } else if let Some(hexkey) = hexkey {
-        if hexkey.len() % 2 == 0 {
-            (
-                hexkey.len() / 2,
-                hex::decode(hexkey).with_context(|| "hex::decode(hexkey)")?,
-            )
-        } else {
-            (
-                hexkey.len() / 2 + 1,
-                hex::decode(hexkey.to_owned() + "0").with_context(|| "hex::decode(hexkey)")?,
-            )
+        // An odd number of digits has a trailing 0
+        let mut digits = SecretBuf::new(hexkey.len() + hexkey.len() % 2);
+        digits[..hexkey.len()].copy_from_slice(hexkey.as_bytes());
+        if hexkey.len() % 2 == 1 {
+            digits[hexkey.len()] = b'0';
         }
+        let mut bytes = SecretBuf::new(digits.len() / 2);
+        hex::decode_to_slice(&*digits, &mut bytes).map_err(|e| AesError::Decode(format!("hex key: {e}")))?;
+        (bytes.len(), bytes)
     } else {
-        return Err("missing required: --key,hexkey (argparse failed)".into());
+        return Err(AesError::InvalidArgument(
+            "missing required: --key,hexkey (argparse failed)".into(),
+        ));
     };
 
-    // Derive from the key length when bits is None
-    let bits = match bits {
-        Some(n) => n,
+    // Derive from the key length when size is None
+    let size = match size {
+        Some(size) => size,
         None => match keylen {
-            n if n <= 16 => 128,
-            n if n > 16 && n <= 24 => 192,
-            _ => 256,
+            n if n <= 16 => KeySize::Aes128,
+            n if n > 16 && n <= 24 => KeySize::Aes192,
+            _ => KeySize::Aes256,
         },
     };
 
-    // Warn if bits doesn't align with key length
+    // Warn if size doesn't align with key length
+    let bits = size.bits();
     if !quiet {
-        if bits == 128 && keylen < 16 || bits == 192 && keylen < 24 || bits == 256 && keylen < 32 {
+        if keylen < size.bytes() {
             eprintln!("{bits}-bit key is too short, padding with zero bytes");
-        } else if bits == 128 && keylen > 16 || bits == 192 && keylen > 24 || bits == 256 && keylen > 32 {
+        } else if keylen > size.bytes() {
             eprintln!("{bits}-bit key is too long, ignoring excess");
         }
     }
 
-    // Copy key into a 32 byte passkey
-    let mut passkey = [0u8; 32];
-    let nbytes = 32.min(key.len().min(bits / 8));
+    // Copy key into exactly size.bytes()
+    let mut passkey = SecretBuf::new(32);
+    let nbytes = key.len().min(size.bytes());
     passkey[..nbytes].copy_from_slice(&key[..nbytes]);
 
-    Ok((bits, passkey))
+    Key::new(size, &passkey[..size.bytes()])
 }
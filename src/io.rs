@@ -0,0 +1,135 @@
+use crate::framing::Framing;
+use crate::key::Key;
+use crate::stream::{Decryptor, Encryptor};
+use std::io::{self, Read, Write};
+use zeroize::Zeroizing;
+
+// std::io adapters producing and consuming the same layout as the CLI
+//
+// EncryptingWriter writes the 1st block (salt or iv) followed by ciphertext to the inner
+// writer, DecryptingReader reads the 1st block and yields plaintext.  Neither holds more
+// than a block plus one read/write worth of data.
+
+// Size of the reads made from the inner reader
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct EncryptingWriter<W: Write> {
+    inner: Option<W>,
+    enc: Option<Encryptor>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> EncryptingWriter<W> {
+    // Writes the 1st block to "inner" immediately
+    //
+    // "ivector" is the supplied iv (or salt with a KDF), it should be random for
+    // --randiv and the KDFs
+    pub fn new(mut inner: W, framing: &Framing, key: &Key, ivector: &[u8; 16]) -> io::Result<Self> {
+        let (header, (key, ivector)) = framing.encrypt_header(key, ivector)?;
+        inner.write_all(&header)?;
+
+        let enc = Encryptor::new(&key, framing.cipher, &ivector).nopkcs(framing.nopkcs);
+        Ok(Self {
+            inner: Some(inner),
+            enc: Some(enc),
+            buf: vec![],
+        })
+    }
+
+    // Write the final padded block, flush, and return the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_final()?;
+        Ok(self.inner.take().expect("inner writer"))
+    }
+
+    fn write_final(&mut self) -> io::Result<()> {
+        if let (Some(enc), Some(inner)) = (self.enc.take(), self.inner.as_mut()) {
+            self.buf.clear();
+            enc.finalize_into(&mut self.buf);
+            inner.write_all(&self.buf)?;
+            inner.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for EncryptingWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let (Some(enc), Some(inner)) = (self.enc.as_mut(), self.inner.as_mut()) else {
+            return Err(io::Error::other("write after finish"));
+        };
+
+        self.buf.clear();
+        enc.update_into(data, &mut self.buf);
+        inner.write_all(&self.buf)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner.as_mut() {
+            Some(inner) => inner.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+// Finish on drop when finish() wasn't called, errors are ignored
+impl<W: Write> Drop for EncryptingWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.write_final();
+    }
+}
+
+pub struct DecryptingReader<R: Read> {
+    inner: R,
+    dec: Option<Decryptor>,
+    buf: Zeroizing<Vec<u8>>, // decrypted bytes not yet returned, wiped on drop
+    pos: usize,
+    chunk: Vec<u8>,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    // Reads the 1st block from "inner" immediately
+    //
+    // "ivector" is the supplied iv, used when the layout has no 1st block
+    pub fn new(mut inner: R, framing: &Framing, key: &Key, ivector: &[u8; 16]) -> io::Result<Self> {
+        let mut block = vec![0; framing.header_len()];
+        inner.read_exact(&mut block)?;
+        let (key, ivector) = framing.decrypt_header(key, ivector, &block)?;
+
+        let dec = Decryptor::new(&key, framing.cipher, &ivector).nopkcs(framing.nopkcs);
+        Ok(Self {
+            inner,
+            dec: Some(dec),
+            buf: Zeroizing::new(vec![]),
+            pos: 0,
+            chunk: vec![0; CHUNK_SIZE],
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            let Some(dec) = self.dec.as_mut() else {
+                return Ok(0);
+            };
+
+            self.buf.clear();
+            self.pos = 0;
+            match self.inner.read(&mut self.chunk)? {
+                0 => self.dec.take().expect("decryptor").finalize_into(&mut self.buf)?,
+                n => dec.update_into(&self.chunk[..n], &mut self.buf),
+            }
+        }
+
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
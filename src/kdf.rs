@@ -1,35 +1,49 @@
+use crate::error::AesError;
+use crate::key::{Key, KeySize};
+use crate::secret::SecretBuf;
 use argon2::Argon2;
-use pbkdf2::pbkdf2_hmac_array;
-use std::error::Error;
+use pbkdf2::pbkdf2_hmac;
+
+// Argon2id parameters of Kdf::ARGON2, the argon2 crate defaults: memory (KiB), passes, lanes
+pub const ARGON2_PARAMS: [u32; 3] = [
+    argon2::Params::DEFAULT_M_COST,
+    argon2::Params::DEFAULT_T_COST,
+    argon2::Params::DEFAULT_P_COST,
+];
 
 // Password-Based Key Derivation Functions
 //
-// Stretches an input password and returns a TUPLE (32-byte password, 16-byte initialization vector)
-#[derive(Clone, Debug)]
+// Stretches an input password and returns a TUPLE (key of the requested size, 16-byte initialization vector)
+// The 48-byte stretched key is held in a locked SecretBuf, wiped once the key and iv are extracted
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Kdf {
     PBKDF2(u32), // https://en.wikipedia.org/wiki/PBKDF2
     ARGON2,      // https://en.wikipedia.org/wiki/Argon2
 }
 impl Kdf {
-    pub fn keyiv(&self, bits: usize, password: &[u8], salt: &[u8]) -> Result<([u8; 32], [u8; 16]), Box<dyn Error>> {
+    pub fn keyiv(&self, size: KeySize, password: &[u8], salt: &[u8]) -> Result<(Key, [u8; 16]), AesError> {
         match self {
-            Self::PBKDF2(iter) => Self::kiv(bits, &pbkdf2_hmac_array::<sha2::Sha256, 48>(password, salt, *iter)),
+            Self::PBKDF2(iter) => {
+                let mut key = SecretBuf::new(48);
+                pbkdf2_hmac::<sha2::Sha256>(password, salt, *iter, &mut key);
+                Ok(Self::kiv(size, &key))
+            }
             Self::ARGON2 => {
-                let mut key = [0u8; 48];
+                let mut key = SecretBuf::new(48);
                 Argon2::default()
                     .hash_password_into(password, salt, &mut key)
-                    .map_err(|e| format!("Argon2: {e}"))?;
-                Self::kiv(bits, &key)
+                    .map_err(|e| AesError::KdfError(format!("Argon2: {e}")))?;
+                Ok(Self::kiv(size, &key))
             }
         }
     }
 
-    // Extract a key/iv pair from a stretched key for bit sizes [128, 192, 256]
-    fn kiv(bits: usize, key: &[u8; 48]) -> Result<([u8; 32], [u8; 16]), Box<dyn Error>> {
-        Ok(match bits {
-            128 => (key[..32].try_into()?, key[16..32].try_into()?),
-            192 => (key[..32].try_into()?, key[24..40].try_into()?),
-            _ => (key[..32].try_into()?, key[32..].try_into()?),
-        })
+    // Extract a key/iv pair from a stretched key, the iv follows the key
+    fn kiv(size: KeySize, key: &[u8]) -> (Key, [u8; 16]) {
+        let n = size.bytes();
+        (
+            Key::new(size, &key[..n]).expect("size.bytes() of key"),
+            key[n..n + 16].try_into().expect("16-byte iv"),
+        )
     }
 }
@@ -0,0 +1,130 @@
+use crate::error::AesError;
+use core::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+// AES key sizes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeySize {
+    Aes128,
+    Aes192,
+    Aes256,
+}
+
+impl KeySize {
+    pub const fn bits(self) -> usize {
+        match self {
+            Self::Aes128 => 128,
+            Self::Aes192 => 192,
+            Self::Aes256 => 256,
+        }
+    }
+
+    // Key length in bytes: 16, 24 or 32
+    pub const fn bytes(self) -> usize {
+        self.bits() / 8
+    }
+
+    pub const fn from_bits(bits: usize) -> Result<Self, AesError> {
+        match bits {
+            128 => Ok(Self::Aes128),
+            192 => Ok(Self::Aes192),
+            256 => Ok(Self::Aes256),
+            _ => Err(AesError::InvalidKeyLength(bits)),
+        }
+    }
+}
+
+// An AES key, only constructed from exactly 16, 24 or 32 bytes
+//
+// Debug shows the size, never the key bytes, and the bytes are wiped on drop
+#[derive(Clone, PartialEq, Eq)]
+pub enum Key {
+    Aes128([u8; 16]),
+    Aes192([u8; 24]),
+    Aes256([u8; 32]),
+}
+
+impl Key {
+    // "bytes" must hold exactly size.bytes()
+    pub fn new(size: KeySize, bytes: &[u8]) -> Result<Self, AesError> {
+        match (size, bytes.len()) {
+            (KeySize::Aes128, 16) | (KeySize::Aes192, 24) | (KeySize::Aes256, 32) => Self::try_from(bytes),
+            (_, n) => Err(AesError::KeySizeMismatch {
+                expected: size.bits(),
+                bits: 8 * n,
+            }),
+        }
+    }
+
+    pub const fn size(&self) -> KeySize {
+        match self {
+            Self::Aes128(_) => KeySize::Aes128,
+            Self::Aes192(_) => KeySize::Aes192,
+            Self::Aes256(_) => KeySize::Aes256,
+        }
+    }
+
+    pub const fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Aes128(key) => key,
+            Self::Aes192(key) => key,
+            Self::Aes256(key) => key,
+        }
+    }
+}
+
+impl From<[u8; 16]> for Key {
+    fn from(key: [u8; 16]) -> Self {
+        Self::Aes128(key)
+    }
+}
+
+impl From<[u8; 24]> for Key {
+    fn from(key: [u8; 24]) -> Self {
+        Self::Aes192(key)
+    }
+}
+
+impl From<[u8; 32]> for Key {
+    fn from(key: [u8; 32]) -> Self {
+        Self::Aes256(key)
+    }
+}
+
+// The key size follows the slice length
+impl TryFrom<&[u8]> for Key {
+    type Error = AesError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, AesError> {
+        match bytes.len() {
+            16 => Ok(Self::Aes128(bytes.try_into().expect("16 bytes"))),
+            24 => Ok(Self::Aes192(bytes.try_into().expect("24 bytes"))),
+            32 => Ok(Self::Aes256(bytes.try_into().expect("32 bytes"))),
+            n => Err(AesError::InvalidKeyLength(8 * n)),
+        }
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Key({:?})", self.size())
+    }
+}
+
+impl Zeroize for Key {
+    fn zeroize(&mut self) {
+        match self {
+            Self::Aes128(key) => key.zeroize(),
+            Self::Aes192(key) => key.zeroize(),
+            Self::Aes256(key) => key.zeroize(),
+        }
+    }
+}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Key {}
@@ -0,0 +1,98 @@
+// The AES core is no_std, see the features in Cargo.toml
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+// AES algorithms
+pub mod libaes;
+
+// Error type returned by the library
+pub mod error;
+pub use error::AesError;
+
+// Key sizes and exact-length keys
+pub mod key;
+pub use key::{Key, KeySize};
+
+// Block cipher trait used by the modes
+pub mod block;
+
+// Modes of operation
+pub mod modes;
+
+// One key, many small records
+#[cfg(feature = "alloc")]
+pub mod batch;
+
+// Compile-time encrypted string literals, aes_literal!
+#[cfg(feature = "alloc")]
+pub mod literal;
+
+// Locked, wiped buffers for key material
+#[cfg(feature = "std")]
+pub mod secret;
+
+// Key Derivation Functions
+#[cfg(feature = "std")]
+pub mod kdf;
+
+// Utility functions
+#[cfg(feature = "std")]
+pub mod general;
+
+// Streaming encryption/decryption
+#[cfg(feature = "alloc")]
+pub mod stream;
+
+// Layout of the 1st block (salt or iv)
+#[cfg(feature = "std")]
+pub mod framing;
+
+// Inputs and output names for many files
+#[cfg(feature = "std")]
+pub mod files;
+
+// Self-describing file header
+#[cfg(feature = "std")]
+pub mod container;
+
+// Seekable authenticated encryption in chunks
+#[cfg(feature = "std")]
+pub mod chunked;
+
+// std::io Read/Write adapters
+#[cfg(feature = "std")]
+pub mod io;
+
+// RustCrypto cipher traits
+#[cfg(feature = "cipher")]
+pub mod rustcrypto;
+
+// serde Encrypted<T> fields
+#[cfg(feature = "serde")]
+pub mod encrypted;
+
+// tokio AsyncRead/AsyncWrite adapters
+#[cfg(feature = "tokio")]
+pub mod async_io;
+
+// Cipher type
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cipher {
+    ECB, // Electronic Codebook
+    CBC, // Cipher Block Chaining
+    CTR, // Integer Counter Mode
+}
+
+// ============
+//  NIST Tests
+// ============
+#[cfg(all(test, feature = "std"))]
+mod nist_tests;
+
+// =============
+// General Tests
+// =============
+#[cfg(all(test, feature = "std"))]
+mod tests;
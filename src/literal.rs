@@ -0,0 +1,92 @@
+use crate::libaes::KeySchedule;
+use crate::modes::ctr;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Strings encrypted at compile time and decrypted at runtime, see aes_literal!
+//
+// This is obfuscation, not secrecy: the key is in the binary next to the ciphertext.
+// It keeps configuration strings out of `strings` output and casual hex dumps.
+
+// Encrypt a string literal at compile time, evaluating to the decrypted String at runtime
+//
+//     let url = aes::aes_literal!(b"0123456789abcdef", "https://config.example/v1");
+//
+// The key is a const &[u8] of 16, 24 or 32 bytes (a byte string literal or a const), any other
+// length fails to compile. CTR mode keeps the ciphertext the length of the text, the iv comes
+// from the call site, see iv()
+#[macro_export]
+macro_rules! aes_literal {
+    ($key:expr, $text:expr $(,)?) => {{
+        const KEY: &[u8] = $key;
+        const TEXT: &str = $text;
+        const IV: [u8; 16] = $crate::literal::iv(file!(), line!(), column!());
+        const SCHEDULE: &$crate::libaes::KeySchedule = &$crate::libaes::KeySchedule::from_bytes(KEY);
+        const CIPHERTEXT: [u8; TEXT.len()] = $crate::literal::encrypt::<{ TEXT.len() }>(SCHEDULE, &IV, TEXT.as_bytes());
+        $crate::literal::decrypt(KEY, &IV, &CIPHERTEXT)
+    }};
+}
+
+// 64-bit FNV-1a
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+const fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut i = 0;
+    while i < bytes.len() {
+        hash = (hash ^ bytes[i] as u64).wrapping_mul(FNV_PRIME);
+        i += 1;
+    }
+    hash
+}
+
+// A CTR iv unique to each call site: the nonce is the FNV-1a hash of file, line and column,
+// the counter starts at 0
+pub const fn iv(file: &str, line: u32, column: u32) -> [u8; 16] {
+    let hash = fnv1a(FNV_OFFSET, file.as_bytes());
+    let hash = fnv1a(hash, &line.to_be_bytes());
+    let nonce = fnv1a(hash, &column.to_be_bytes()).to_be_bytes();
+
+    let mut iv = [0u8; 16];
+    let mut i = 0;
+    while i < 8 {
+        iv[i] = nonce[i];
+        i += 1;
+    }
+    iv
+}
+
+// CTR encryption of "text" (N bytes) as a const fn, the same keystream as modes::ctr
+pub const fn encrypt<const N: usize>(key: &KeySchedule, iv: &[u8; 16], text: &[u8]) -> [u8; N] {
+    assert!(text.len() == N, "text is N bytes");
+
+    let mut counter = u64::from_be_bytes([iv[8], iv[9], iv[10], iv[11], iv[12], iv[13], iv[14], iv[15]]);
+    let mut out = [0u8; N];
+    let mut i = 0;
+    while i < N {
+        let mut block = *iv;
+        let ctr = counter.to_be_bytes();
+        let mut j = 0;
+        while j < 8 {
+            block[8 + j] = ctr[j];
+            j += 1;
+        }
+        key.encrypt_block(&mut block);
+
+        let mut j = 0;
+        while j < 16 && i + j < N {
+            out[i + j] = text[i + j] ^ block[j];
+            j += 1;
+        }
+        counter = counter.wrapping_add(1);
+        i += 16;
+    }
+    out
+}
+
+// The runtime half of aes_literal!
+pub fn decrypt(key: &[u8], iv: &[u8; 16], ciphertext: &[u8]) -> String {
+    let mut text = Vec::from(ciphertext);
+    ctr::apply(&KeySchedule::from_bytes(key), &mut text, iv);
+    String::from_utf8(text).expect("aes_literal! encrypts a &str")
+}
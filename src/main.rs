@@ -1,46 +1,59 @@
-use anyhow::Result;
-use base64::{engine::general_purpose, Engine as _};
+use aes::chunked::{ChunkedReader, ChunkedWriter, PREFIX_SIZE};
+use aes::container::{self, Header, Mode, HEADER_LEN};
+use aes::files;
+use aes::framing::Framing;
+use aes::general::{
+    disable_core_dumps, get_ivector, get_key, reset_sigpipe, AtomicFile, Encoding, EncodingWriter, Input, CHUNK_SIZE,
+};
+use aes::kdf::Kdf;
+use aes::stream::{Decryptor, Encryptor};
+use aes::{AesError, Cipher, Key, KeySize};
 use clap::Id;
-use std::error::Error;
-use std::io::{self, Write};
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use zeroize::Zeroizing;
 
 // Clap arg parser
 mod argparse;
 
-// AES algorithms
-mod libaes;
-use libaes::{aes_decrypt, aes_encrypt};
-
-// Key Derivation Functions
-mod kdf;
-use kdf::Kdf;
-
-// Utility functions
-mod general;
-use general::{get_ivector, get_passkey32, read_input_bytes, reset_sigpipe};
+// ==============================================================
 
-// Cipher type
-#[derive(Debug, PartialEq)]
-pub enum Cipher {
-    ECB, // Electronic Codebook
-    CBC, // Cipher Block Chaining
-    CTR, // Integer Counter Mode
+fn main() -> ExitCode {
+    match run() {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("aes: {e}");
+            ExitCode::from(exit_code(&e))
+        }
+    }
 }
 
-// Helper function: number of blocks for n-bytes
-const fn blocks(n: usize) -> usize {
-    match n % 16 == 0 {
-        true => n / 16,
-        false => n / 16 + 1, // padded block
+// Distinct exit status for each error, 2 matches Clap's usage errors
+fn exit_code(e: &AesError) -> u8 {
+    match e {
+        AesError::InvalidArgument(_) => 2,
+        AesError::InvalidKeyLength(_) | AesError::KeySizeMismatch { .. } => 3,
+        AesError::InvalidCiphertextLength(_) => 4,
+        AesError::BufferTooSmall { .. } => 5,
+        AesError::BadPadding => 6,
+        AesError::AuthenticationFailed => 7,
+        AesError::EmptyInput => 8,
+        AesError::KdfError(_) => 9,
+        AesError::Decode(_) => 10,
+        AesError::Io(_) => 11,
+        AesError::Format(_) => 12,
+        _ => 1,
     }
 }
 
-// ==============================================================
-
-fn main() -> Result<(), Box<dyn Error>> {
+fn run() -> Result<ExitCode, AesError> {
     // Behave like a typical unix utility
     reset_sigpipe()?;
-    let mut stdout = io::stdout().lock();
+    // Keys are resident from here on
+    disable_core_dumps()?;
 
     // Parse command line arguments enforcing contraints with Clap, see src/argparse.rs
     let args = argparse::get_args();
@@ -65,26 +78,32 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Is a random initialization vector being created?
     let randiv: bool = args.get_flag("randiv");
 
-    // Set the cipher mode (ecb, cbc, ctr)
+    // Authenticated chunks in a container instead of a cipher, see src/chunked.rs
+    let chunked = args.get_flag("chunked");
+
+    // Set the cipher mode (ecb, cbc, ctr), optional when decrypting a container or with --chunked
     let cipher = if args.get_flag("ecb") || ciph_desc.contains("ecb") {
-        Cipher::ECB
+        Some(Cipher::ECB)
     } else if args.get_flag("cbc") || ciph_desc.contains("cbc") {
-        Cipher::CBC
+        Some(Cipher::CBC)
     } else if args.get_flag("ctr") || ciph_desc.contains("ctr") {
-        Cipher::CTR
+        Some(Cipher::CTR)
     } else if ciph_desc.is_empty() {
-        return Err("missing cipher: --ecb,cbc,ctr or --aes-{128,192,256}-{ecb,cbc,ctr}".into());
+        match encrypt && !chunked {
+            true => return Err(missing_cipher()),
+            false => None,
+        }
     } else {
         unreachable!("argparse failed")
     };
 
-    // Number of bits is specified with flags or will be derived from key length
-    let bits_specified = if args.get_flag("128") || ciph_desc.contains("128") {
-        Some(128)
+    // Key size is specified with flags or will be derived from key length
+    let size_specified = if args.get_flag("128") || ciph_desc.contains("128") {
+        Some(KeySize::Aes128)
     } else if args.get_flag("192") || ciph_desc.contains("192") {
-        Some(192)
+        Some(KeySize::Aes192)
     } else if args.get_flag("256") || ciph_desc.contains("256") {
-        Some(256)
+        Some(KeySize::Aes256)
     } else {
         None
     };
@@ -100,175 +119,415 @@ fn main() -> Result<(), Box<dyn Error>> {
         None
     };
 
-    // This offset value is used in input/output/padding calculations
-    let first_block_sz = if kdf.is_some() || (randiv && cipher != Cipher::ECB) {
-        16
-    } else {
-        0
+    // Create a 16, 24 or 32-byte key, a container's header has the size.  Decrypting, warnings
+    // about the size wait until an input isn't a container, see crypt()
+    let key = match cipher.is_some() || chunked {
+        true => Some(get_key(
+            size_specified,
+            args.get_one::<String>("key"),
+            args.get_one::<String>("hexkey"),
+            quiet || kdf.is_some() || !encrypt,
+        )?),
+        false => None,
     };
 
-    // Create a 32-byte passkey and set bit size to [128, 192, 256]
-    let (bits, passkey) = get_passkey32(
-        bits_specified,
-        args.get_one::<String>("key"),
-        args.get_one::<String>("hexkey"),
-        quiet || kdf.is_some(),
-    )?;
-
-    // Read the input FILE as bytes and perform any Base-64/Hex decodings
-    let bytes = read_input_bytes(
-        args.get_one::<std::path::PathBuf>("FILE"),
-        args.get_flag("ibase64"),
-        args.get_flag("ihex"),
-    )?;
+    // Layout of the 1st block, see src/framing.rs
+    let framing = cipher.map(|cipher| Framing {
+        cipher,
+        kdf: kdf.clone(),
+        randiv,
+        nopkcs: args.get_flag("nopkcs"),
+    });
+
+    let opts = Opts {
+        encrypt,
+        framing,
+        chunked,
+        kdf,
+        key,
+        passkey: args.get_one::<String>("key").cloned(),
+        hexkey: args.get_one::<String>("hexkey").cloned(),
+        container: args.get_flag("container") || chunked,
+        quiet,
+        iv: args.get_one::<String>("iv").cloned(),
+        ibase64: args.get_flag("ibase64"),
+        ihex: args.get_flag("ihex"),
+        encoding: match (args.get_flag("obase64"), args.get_flag("ohex")) {
+            (true, _) => Some(Encoding::Base64),
+            (_, true) => Some(Encoding::Hex),
+            _ => None,
+        },
+        print: args.get_flag("P"),
+        size: size_specified,
+        warned: Cell::new(false),
+        salt: rand::random(),
+        derived: RefCell::new(None),
+    };
 
-    // That was fun
-    if bytes.is_empty() {
-        return Err("aes: empty input".into());
+    let paths: Vec<PathBuf> = args
+        .get_many::<PathBuf>("FILE")
+        .map(|paths| paths.cloned().collect())
+        .unwrap_or_default();
+    let recursive = args.get_flag("recursive");
+    let out_dir = args.get_one::<PathBuf>("out-dir");
+    let in_place = args.get_flag("in-place");
+    let force = args.get_flag("force");
+
+    // A single FILE (or stdin) written to stdout, the --out file or in place
+    if paths.len() <= 1 && !recursive && out_dir.is_none() {
+        let file = paths.first();
+        let outfile = match (args.get_one::<PathBuf>("out"), in_place) {
+            (Some(out), _) if out.as_os_str() != "-" => Some(AtomicFile::create(out, force)?),
+            (_, true) => match file {
+                // Through symlinks, the file itself is replaced
+                Some(file) if file.as_os_str() != "-" => Some(AtomicFile::create(&file.canonicalize()?, true)?),
+                _ => {
+                    return Err(AesError::InvalidArgument(
+                        "--in-place needs a FILE, not standard input".into(),
+                    ))
+                }
+            },
+            _ => None,
+        };
+        return crypt(&opts, file, outfile).map(|()| ExitCode::SUCCESS);
+    }
+
+    // Many files, each to its own output file, see src/files.rs
+    if args.get_one::<PathBuf>("out").is_some() || opts.print {
+        return Err(AesError::InvalidArgument(
+            "-o and -P take a single FILE, see --out-dir".into(),
+        ));
+    }
+    // One key and iv for every file would reuse the CTR keystream
+    if encrypt && !chunked && cipher == Some(Cipher::CTR) && opts.iv.is_some() {
+        return Err(AesError::InvalidArgument(
+            "--iv with CTR takes a single FILE, each file needs its own iv".into(),
+        ));
+    }
+    let jobs = files::plan(&paths, encrypt, recursive, out_dir, in_place);
+    if jobs.is_empty() {
+        return Err(AesError::InvalidArgument("no files found".into()));
+    }
+
+    // Every file is attempted, the exit status is that of the first failure
+    let mut failures = 0;
+    let mut status = ExitCode::SUCCESS;
+    let total = jobs.len();
+    for (input, output) in jobs {
+        let result = output.and_then(|output| {
+            if let Some(dir) = output.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let outfile = AtomicFile::create(&output, force || in_place)?;
+            crypt(&opts, Some(&input), Some(outfile)).map(|()| output)
+        });
+        match result {
+            Ok(output) => eprintln!("{} -> {}", input.display(), output.display()),
+            Err(e) => {
+                eprintln!("aes: {}: {e}", input.display());
+                if failures == 0 {
+                    status = ExitCode::from(exit_code(&e));
+                }
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        eprintln!("aes: {failures} of {total} files failed");
+    }
+    Ok(status)
+}
+
+// A container key and the KDF, key size and salt it was derived with
+type Derived = (Kdf, KeySize, [u8; 16], Key);
+
+// Settings shared by every file
+struct Opts {
+    encrypt: bool,
+    framing: Option<Framing>, // None without a cipher, only containers can be decrypted
+    chunked: bool,            // encrypt with ChunkedWriter, implies container
+    kdf: Option<Kdf>,         // as in "framing", for --chunked
+    key: Option<Key>,
+    passkey: Option<String>, // -k, -K again for the key size in a container header
+    hexkey: Option<String>,
+    container: bool,
+    quiet: bool,
+    iv: Option<String>,
+    ibase64: bool,
+    ihex: bool,
+    encoding: Option<Encoding>,
+    print: bool,                       // -P
+    size: Option<KeySize>,             // --128,192,256 or from --aes-*
+    warned: Cell<bool>,                // about the key size, once
+    salt: [u8; 16],                    // of every container written with a KDF
+    derived: RefCell<Option<Derived>>, // the last container key derived
+}
+
+impl Opts {
+    // The layout and key given by the flags
+    fn framing(&self) -> Result<(&Framing, &Key), AesError> {
+        match (&self.framing, &self.key) {
+            (Some(framing), Some(key)) => Ok((framing, key)),
+            _ => Err(missing_cipher()),
+        }
     }
 
-    // Initialize the output byte buffer
-    let mut output = vec![];
+    // The key of a container, derived only when the KDF, key size or salt differ from the last
+    // one: once for all the files encrypted, once per batch of them decrypted
+    fn container_key(&self, header: &Header, key: &Key) -> Result<Key, AesError> {
+        let Some(ref kdf) = header.kdf else {
+            return Ok(key.clone());
+        };
+        let mut derived = self.derived.borrow_mut();
+        match *derived {
+            Some((ref k, size, salt, ref key)) if *k == *kdf && size == header.size && salt == header.salt => {
+                Ok(key.clone())
+            }
+            _ => {
+                let stretched = header.key(key)?;
+                *derived = Some((kdf.clone(), header.size, header.salt, stretched.clone()));
+                Ok(stretched)
+            }
+        }
+    }
+}
+
+fn missing_cipher() -> AesError {
+    AesError::InvalidArgument("missing cipher: --ecb,cbc,ctr or --aes-{128,192,256}-{ecb,cbc,ctr}".into())
+}
+
+// Encrypt or decrypt "file" (None is stdin) to "outfile", committed on success, or stdout
+//
+// Each call makes its own random iv.  A container's KDF only derives the key, from the salt
+// shared by the run (Opts::container_key), the 1st block layout derives key and iv from a salt
+// of the file's own, so with a KDF every file is derived again
+fn crypt(opts: &Opts, file: Option<&PathBuf>, mut outfile: Option<AtomicFile>) -> Result<(), AesError> {
+    let encrypt = opts.encrypt;
+
+    // Open the input FILE (memory mapped when possible) and perform any Base-64/Hex decodings,
+    // decrypting a container (raw, Base-64 or Hex) is noticed by its magic, see src/container.rs
+    let (mut input, container) = match encrypt {
+        true => (Input::open(file, opts.ibase64, opts.ihex)?, opts.container),
+        false => Input::open_detect(file, opts.ibase64, opts.ihex, &container::MAGIC)?,
+    };
+
+    // That was fun
+    if input.is_empty()? {
+        return Err(AesError::EmptyInput);
+    }
 
     // ============================================================================
-    // Step 1 - Obtain the ivector and passkey, r/w block-1
+    // Step 1 - Obtain the ivector and key, r/w block-1
     //
     // Note: iv resides in the 1st block when invoked with --randiv for [CBC, CTR],
     //       salt is the last 8 bytes of the 1st block when using --pbkdf2
     //       salt is the 1st block when using --argon2
+    //       a container's header holds the cipher, key size, KDF, salt and iv
     // ============================================================================
-    let mut ivector = get_ivector(
-        encrypt && (randiv || kdf.is_some()), // conditions for a random iv
-        args.get_one::<String>("iv"),         // supplied iv
-        quiet,                                // squelch warnings re short/truncated keys?
-    )?;
-    let (passkey, ivector) = match encrypt {
-        true => match kdf {
-            Some(ref hasher) => {
-                match hasher {
-                    Kdf::PBKDF2(_) => {
-                        let salt: [u8; 8] = ivector[8..].try_into()?;
-                        // Copy b"Salted__xxxxxxxx" to the 1st block of output
-                        output.extend(b"Salted__");
-                        output.extend(&salt);
-                        hasher.keyiv(bits, &passkey, &salt)?
-                    }
-                    Kdf::ARGON2 => {
-                        // Copy ivector to the 1st block of output
-                        output.extend(&ivector);
-                        hasher.keyiv(bits, &passkey, &ivector)?
-                    }
-                }
-            }
-            None => {
-                // Copy the ivector to the 1st block of output
-                if first_block_sz > 0 {
-                    output.extend(&ivector);
+    let (header, mode, nopkcs, salt, (key, ivector)) = match (encrypt, container) {
+        (true, true) => {
+            let (mode, nopkcs, kdf, key) = match opts.chunked {
+                true => (
+                    Mode::Chunked,
+                    false,
+                    &opts.kdf,
+                    opts.key.as_ref().ok_or_else(missing_cipher)?,
+                ),
+                false => {
+                    let (framing, key) = opts.framing()?;
+                    (Mode::Cipher(framing.cipher), framing.nopkcs, &framing.kdf, key)
                 }
-                (passkey, ivector)
-            }
-        },
-        false => {
-            // Read the iv (or salt for kdf's) from the 1st block of input
-            if first_block_sz > 0 {
-                ivector.copy_from_slice(&bytes[..16]);
+            };
+            let header = Header {
+                mode,
+                size: key.size(),
+                nopkcs,
+                encoding: opts.encoding,
+                kdf: kdf.clone(),
+                salt: match kdf {
+                    Some(_) => opts.salt,
+                    None => [0; 16],
+                },
+                iv: match mode {
+                    // A random nonce prefix, the rest is 0
+                    Mode::Chunked => {
+                        let mut iv = [0; 16];
+                        iv[..PREFIX_SIZE].copy_from_slice(&rand::random::<[u8; PREFIX_SIZE]>());
+                        iv
+                    }
+                    Mode::Cipher(cipher) => get_ivector(cipher != Cipher::ECB, opts.iv.as_ref(), opts.quiet)?,
+                },
+            };
+            // Nothing readers reject is written, e.g. --iter above container::MAX_PBKDF2_ITER
+            let bytes = header.to_bytes();
+            Header::from_bytes(&bytes)?;
+
+            let salt = header.kdf.as_ref().map(|_| header.salt.to_vec());
+            let keyiv = (opts.container_key(&header, key)?, header.iv);
+            (bytes.to_vec(), header.mode, header.nopkcs, salt, keyiv)
+        }
+        (false, true) => {
+            let block = input.read_up_to(HEADER_LEN)?;
+            let header = Header::from_bytes(&block)?;
+            // The header has the size, warning about it was encrypt's job
+            let key = get_key(Some(header.size), opts.passkey.as_ref(), opts.hexkey.as_ref(), true)?;
+            let salt = header.kdf.as_ref().map(|_| header.salt.to_vec());
+            let keyiv = (opts.container_key(&header, &key)?, header.iv);
+            (block, header.mode, header.nopkcs, salt, keyiv)
+        }
+        (_, false) => {
+            // Not derived once per run like a container key: this KDF derives the iv too, from
+            // the salt in the file, one derivation would give every file the same key and iv
+            let (framing, key) = opts.framing()?;
+
+            // Decrypting, the key sized by the flags is used after all
+            if !encrypt && !opts.quiet && framing.kdf.is_none() && !opts.warned.replace(true) {
+                get_key(opts.size, opts.passkey.as_ref(), opts.hexkey.as_ref(), false)?;
             }
-            match kdf {
-                Some(ref hasher) => {
-                    match hasher {
-                        // ivector contains the salt, skip over b"Salted__"
-                        Kdf::PBKDF2(_) => hasher.keyiv(bits, &passkey, &ivector[8..])?,
-                        Kdf::ARGON2 => hasher.keyiv(bits, &passkey, &ivector)?,
+
+            // This offset value is used in input/output/padding calculations
+            let first_block_sz = framing.header_len();
+
+            let ivector = get_ivector(
+                encrypt && (framing.randiv || framing.kdf.is_some()), // conditions for a random iv
+                opts.iv.as_ref(),                                     // supplied iv
+                opts.quiet,                                           // squelch warnings re short/truncated keys?
+            )?;
+            let (header, keyiv) = match encrypt {
+                true => framing.encrypt_header(key, &ivector)?,
+                false => {
+                    // Read the iv (or salt for kdf's) from the 1st block of input
+                    let block = input.read_up_to(first_block_sz)?;
+                    if block.len() < first_block_sz {
+                        return Err(AesError::InvalidCiphertextLength(block.len()));
                     }
+                    let keyiv = framing.decrypt_header(key, &ivector, &block)?;
+                    (block, keyiv)
                 }
-                None => (passkey, ivector),
-            }
+            };
+            let salt = match framing.kdf {
+                Some(Kdf::PBKDF2(_)) => Some(header[8..16].to_vec()),
+                Some(Kdf::ARGON2) => Some(header[..16].to_vec()),
+                None => None,
+            };
+            (header, Mode::Cipher(framing.cipher), framing.nopkcs, salt, keyiv)
         }
     };
 
     // Option -P prints cipher details to stderr and returns
-    if args.get_flag("P") {
-        let block = match encrypt {
-            true => output,
-            false => bytes,
-        };
-
-        eprintln!("AES-{cipher:?}-{bits}");
-
-        if let Some(ref hasher) = kdf {
-            match hasher {
-                Kdf::PBKDF2(_) => eprintln!("salt={}", hex::encode(&block[8..16]).to_uppercase()),
-                Kdf::ARGON2 => eprintln!("salt={}", hex::encode(&block[..16]).to_uppercase()),
-            }
+    if opts.print {
+        match mode {
+            Mode::Cipher(cipher) => eprintln!("AES-{cipher:?}-{}", key.size().bits()),
+            Mode::Chunked => eprintln!("AES-GCM-{} (chunked)", key.size().bits()),
         }
-
-        match bits {
-            128 => eprintln!("key={}", hex::encode(&passkey[0..16]).to_uppercase()),
-            192 => eprintln!("key={}", hex::encode(&passkey[0..24]).to_uppercase()),
-            _ => eprintln!("key={}", hex::encode(passkey).to_uppercase()),
+        if let Some(salt) = salt {
+            eprintln!("salt={}", hex::encode(salt).to_uppercase());
         }
-
+        eprintln!("key={}", hex::encode(key.as_bytes()).to_uppercase());
         eprintln!("iv ={}", hex::encode(ivector).to_uppercase());
         return Ok(());
     }
 
-    // =================================================
-    // Step 2 - Encrypt / Decrypt and handle final block
-    // =================================================
-    if encrypt {
-        // Add encrypted bytes to output
-        output.extend(aes_encrypt(bits, &passkey, &bytes, &cipher, &ivector));
+    // ====================================================================
+    // Step 2 - Encrypt / Decrypt a chunk at a time and handle final block
+    //
+    // Output is written (Base-64/Hex encoded in 76 column lines) and flushed
+    // as each chunk is processed so aes can sit in a pipeline
+    // ====================================================================
+    // Wiped on drop, holds plaintext
+    let mut output = Zeroizing::new(Vec::with_capacity(CHUNK_SIZE + 16));
+    let prefix: [u8; PREFIX_SIZE] = ivector[..PREFIX_SIZE].try_into().expect("7-byte prefix");
+    let mut process = |out: &mut dyn Write| -> Result<(), AesError> {
+        match (mode, encrypt) {
+            // The header is the associated data of every chunk
+            (Mode::Chunked, true) => {
+                out.write_all(&header)?;
+                let mut writer = ChunkedWriter::new(&mut *out, &key, &prefix, &header);
+                loop {
+                    let chunk = input.next_chunk()?;
+                    if chunk.is_empty() {
+                        break;
+                    }
+                    writer.write_all(chunk)?;
+                    writer.flush()?;
+                }
+                writer.finish()?;
+            }
+            // Each chunk is verified before it's written, a truncated file fails at its end
+            (Mode::Chunked, false) => {
+                let mut reader = ChunkedReader::new(&mut input, &key, &prefix, &header);
+                output.resize(CHUNK_SIZE, 0);
+                loop {
+                    let n = reader.read(&mut output)?;
+                    if n == 0 {
+                        break;
+                    }
+                    out.write_all(&output[..n])?;
+                    out.flush()?;
+                }
+            }
+            (Mode::Cipher(cipher), true) => {
+                let mut enc = Encryptor::new(&key, cipher, &ivector).nopkcs(nopkcs);
+                out.write_all(&header)?;
+                loop {
+                    let chunk = input.next_chunk()?;
+                    if chunk.is_empty() {
+                        break;
+                    }
+                    output.clear();
+                    enc.update_into(chunk, &mut output);
+                    out.write_all(&output)?;
+                    out.flush()?;
+                }
+
+                // Pad the final block, no full pad block with --nopkcs
+                output.clear();
+                enc.finalize_into(&mut output);
+                out.write_all(&output)?;
+            }
+            (Mode::Cipher(cipher), false) => {
+                let mut dec = Decryptor::new(&key, cipher, &ivector).nopkcs(nopkcs);
+                loop {
+                    let chunk = input.next_chunk()?;
+                    if chunk.is_empty() {
+                        break;
+                    }
+                    output.clear();
+                    dec.update_into(chunk, &mut output);
+                    out.write_all(&output)?;
+                    out.flush()?;
+                }
 
-        // Protect against output of a full pad block
-        if args.get_flag("nopkcs") && cipher != Cipher::CTR {
-            output.drain(16 * blocks(bytes.len() + first_block_sz)..);
-        }
-    } else {
-        // Add decrypted bytes to output
-        output.extend(aes_decrypt(bits, &passkey, &bytes[first_block_sz..], &cipher, &ivector));
-
-        // Pad removal
-        if cipher != Cipher::CTR {
-            if args.get_flag("nopkcs") {
-                output.drain((16 * blocks(bytes.len() - first_block_sz))..);
-            } else {
-                // The last byte value is the count of pad chars to remove
-                let padcount = *output.last().ok_or("pad count error")? as usize;
-                output.drain((16 * blocks(bytes.len() - first_block_sz) - padcount)..);
+                // Pad removal
+                output.clear();
+                dec.finalize_into(&mut output)?;
+                out.write_all(&output)?;
             }
         }
-    }
-
-    // =================================
-    // Step 3 - Output as encoded or raw
-    // =================================
-    if args.get_flag("obase64") || args.get_flag("ohex") {
-        let mut s = match args.get_flag("ohex") {
-            true => hex::encode(output),
-            false => general_purpose::STANDARD.encode(&output),
-        };
+        out.flush()?;
+        Ok(())
+    };
 
-        // Split Base-64 and Hex into 76 byte chunks
-        while !s.is_empty() {
-            let (chunk, rest) = s.split_at(std::cmp::min(76, s.len()));
-            writeln!(stdout, "{chunk}")?;
-            s = rest.into();
+    let mut stdout = io::stdout().lock();
+    let sink: &mut dyn Write = match outfile {
+        Some(ref mut outfile) => outfile,
+        None => &mut stdout,
+    };
+    match opts.encoding {
+        Some(encoding) => {
+            let mut encoder = EncodingWriter::new(sink, encoding);
+            process(&mut encoder)?;
+            encoder.finish()?;
         }
-    } else {
-        stdout.write_all(&output)?;
+        None => process(sink)?,
+    }
+
+    // =========================================================================
+    // Step 3 - Replace the output file now that everything was written (-o, --in-place)
+    // =========================================================================
+    if let Some(outfile) = outfile {
+        outfile.commit()?;
     }
 
     Ok(())
 }
-
-// ============
-//  NIST Tests
-// ============
-#[cfg(test)]
-mod nist_tests;
-
-// =============
-// General Tests
-// =============
-#[cfg(test)]
-mod tests;
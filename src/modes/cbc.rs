@@ -0,0 +1,42 @@
+use crate::block::BlockCipher;
+
+// Each plaintext block is XOR'd with the previous ciphertext block (the iv for the first)
+//
+// "buf" must be a multiple of the block size.  To continue a stream pass the last
+// ciphertext block as the next iv.
+pub fn encrypt<C: BlockCipher>(cipher: &C, buf: &mut [u8], iv: &[u8]) {
+    let bs = C::BLOCK_SIZE;
+    debug_assert_eq!(iv.len(), bs);
+
+    for n in 0..buf.len() / bs {
+        let (prev, rest) = buf.split_at_mut(n * bs);
+        let chain = match n {
+            0 => iv,
+            _ => &prev[prev.len() - bs..],
+        };
+        let block = &mut rest[..bs];
+        for (b, c) in block.iter_mut().zip(chain) {
+            *b ^= c;
+        }
+        cipher.encrypt_block(block);
+    }
+}
+
+// Blocks are decrypted last to first so the previous ciphertext block is still in place
+pub fn decrypt<C: BlockCipher>(cipher: &C, buf: &mut [u8], iv: &[u8]) {
+    let bs = C::BLOCK_SIZE;
+    debug_assert_eq!(iv.len(), bs);
+
+    for n in (0..buf.len() / bs).rev() {
+        let (prev, rest) = buf.split_at_mut(n * bs);
+        let block = &mut rest[..bs];
+        cipher.decrypt_block(block);
+        let chain = match n {
+            0 => iv,
+            _ => &prev[prev.len() - bs..],
+        };
+        for (b, c) in block.iter_mut().zip(chain) {
+            *b ^= c;
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::block::BlockCipher;
+
+// Counter blocks are the iv with its last 8 bytes replaced by a big-endian u64 counter,
+// starting from the iv's value and wrapping.  Encrypt and decrypt are the same XOR.
+//
+// The keystream for up to KEYSTREAM bytes of blocks is generated per encrypt_blocks()
+// call.  Returns the counter following the last block used, a partial final block
+// consumes a whole counter.
+const KEYSTREAM: usize = 64;
+
+pub fn apply<C: BlockCipher>(cipher: &C, buf: &mut [u8], iv: &[u8]) -> u64 {
+    keystream(cipher, buf, iv, 8)
+}
+
+// apply() with only the last 4 bytes counting, GCM's inc32: the counter wraps without
+// carrying into the rest of the iv
+pub fn apply32<C: BlockCipher>(cipher: &C, buf: &mut [u8], iv: &[u8]) -> u32 {
+    keystream(cipher, buf, iv, 4) as u32
+}
+
+// XOR the keystream with a big-endian counter of "width" bytes at the end of the iv
+fn keystream<C: BlockCipher>(cipher: &C, buf: &mut [u8], iv: &[u8], width: usize) -> u64 {
+    let bs = C::BLOCK_SIZE;
+    debug_assert!(iv.len() == bs && (8..=KEYSTREAM).contains(&bs) && width <= 8);
+
+    let (nonce, start) = iv.split_at(bs - width);
+    let mut counter = start.iter().fold(0u64, |n, &b| (n << 8) | b as u64);
+    let mask = u64::MAX >> (64 - 8 * width);
+    let mut keystream = [0u8; KEYSTREAM];
+    let lanes = KEYSTREAM / bs;
+
+    for chunk in buf.chunks_mut(bs * lanes) {
+        let nblocks = chunk.len().div_ceil(bs);
+        for ctr_block in keystream.chunks_exact_mut(bs).take(nblocks) {
+            ctr_block[..bs - width].copy_from_slice(nonce);
+            ctr_block[bs - width..].copy_from_slice(&counter.to_be_bytes()[8 - width..]);
+            counter = counter.wrapping_add(1) & mask;
+        }
+
+        cipher.encrypt_blocks(&mut keystream[..bs * nblocks]);
+        for (b, k) in chunk.iter_mut().zip(keystream) {
+            *b ^= k;
+        }
+    }
+    counter
+}
@@ -0,0 +1,10 @@
+use crate::block::BlockCipher;
+
+// Each block is encrypted independently, "buf" must be a multiple of the block size
+pub fn encrypt<C: BlockCipher>(cipher: &C, buf: &mut [u8]) {
+    cipher.encrypt_blocks(buf);
+}
+
+pub fn decrypt<C: BlockCipher>(cipher: &C, buf: &mut [u8]) {
+    cipher.decrypt_blocks(buf);
+}
@@ -0,0 +1,94 @@
+use crate::block::BlockCipher;
+use crate::error::AesError;
+use crate::modes::ctr;
+
+// Galois/Counter Mode, NIST SP 800-38D with a 96-bit nonce and a 128-bit tag
+//
+// The data is CTR encrypted from counter block 2 (the nonce followed by a 32-bit big-endian
+// counter), the tag is GHASH over the associated data and ciphertext XOR'd with the
+// encrypted counter block 1.  The block cipher must have 16-byte blocks.
+//
+// A nonce must never be reused with the same key.
+
+pub const NONCE_SIZE: usize = 12;
+pub const TAG_SIZE: usize = 16;
+
+// Encrypt "buf" in place, returning the tag
+pub fn encrypt<C: BlockCipher>(cipher: &C, nonce: &[u8; NONCE_SIZE], aad: &[u8], buf: &mut [u8]) -> [u8; TAG_SIZE] {
+    debug_assert_eq!(C::BLOCK_SIZE, 16);
+
+    keystream(cipher, nonce, buf);
+    tag(cipher, nonce, aad, buf)
+}
+
+// Verify "tag" then decrypt "buf" in place, "buf" is left untouched when verification fails
+pub fn decrypt<C: BlockCipher>(
+    cipher: &C,
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+    buf: &mut [u8],
+    tag: &[u8; TAG_SIZE],
+) -> Result<(), AesError> {
+    debug_assert_eq!(C::BLOCK_SIZE, 16);
+
+    // Compare every byte, the position of a mismatch isn't leaked
+    let expected = self::tag(cipher, nonce, aad, buf);
+    match expected.iter().zip(tag).fold(0, |acc, (a, b)| acc | (a ^ b)) {
+        0 => {
+            keystream(cipher, nonce, buf);
+            Ok(())
+        }
+        _ => Err(AesError::AuthenticationFailed),
+    }
+}
+
+// The nonce followed by a 32-bit big-endian counter
+fn counter_block(nonce: &[u8; NONCE_SIZE], counter: u32) -> [u8; 16] {
+    let mut block = [0u8; 16];
+    block[..NONCE_SIZE].copy_from_slice(nonce);
+    block[NONCE_SIZE..].copy_from_slice(&counter.to_be_bytes());
+    block
+}
+
+// XOR the keystream starting from counter 2, see ctr::apply32()
+fn keystream<C: BlockCipher>(cipher: &C, nonce: &[u8; NONCE_SIZE], buf: &mut [u8]) {
+    ctr::apply32(cipher, buf, &counter_block(nonce, 2));
+}
+
+// GHASH(H, aad, ciphertext) XOR E(counter block 1)
+fn tag<C: BlockCipher>(cipher: &C, nonce: &[u8; NONCE_SIZE], aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_SIZE] {
+    let mut h = [0u8; 16];
+    cipher.encrypt_block(&mut h);
+    let h = u128::from_be_bytes(h);
+
+    let mut ghash = 0;
+    for data in [aad, ciphertext] {
+        for chunk in data.chunks(16) {
+            let mut block = [0u8; 16];
+            block[..chunk.len()].copy_from_slice(chunk);
+            ghash = gf_mul(ghash ^ u128::from_be_bytes(block), h);
+        }
+    }
+    let lengths = ((aad.len() as u128 * 8) << 64) | (ciphertext.len() as u128 * 8);
+    ghash = gf_mul(ghash ^ lengths, h);
+
+    let mut j0 = counter_block(nonce, 1);
+    cipher.encrypt_block(&mut j0);
+    (ghash ^ u128::from_be_bytes(j0)).to_be_bytes()
+}
+
+// Multiplication in GF(2^128) with GCM's reflected bit order, x^128 + x^7 + x^2 + x + 1
+//
+// A bit at a time with masks rather than branches, the timing doesn't depend on x or y
+fn gf_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+
+    let mut z = 0;
+    let mut v = y;
+    for i in 0..128 {
+        let bit = (x >> (127 - i)) & 1;
+        z ^= v & 0u128.wrapping_sub(bit);
+        v = (v >> 1) ^ (R & 0u128.wrapping_sub(v & 1));
+    }
+    z
+}
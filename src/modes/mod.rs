@@ -0,0 +1,19 @@
+// Modes of operation, generic over block::BlockCipher
+//
+// Each mode works in place on whole blocks (CTR on any length) and takes the iv
+// (or chaining value) as a BLOCK_SIZE slice.  Padding is separate, see pkcs7.
+
+// Electronic Codebook
+pub mod ecb;
+
+// Cipher Block Chaining
+pub mod cbc;
+
+// Integer Counter Mode
+pub mod ctr;
+
+// Galois/Counter Mode (authenticated)
+pub mod gcm;
+
+// PKCS#7 padding
+pub mod pkcs7;
@@ -0,0 +1,40 @@
+// PKCS#7 padding: fill the remainder of "buf" following "len" data bytes with the pad count
+//
+// "buf" extends "len" to the next multiple of the block size, a full block of padding
+// when len is already a multiple
+pub fn pad(buf: &mut [u8], len: usize) {
+    let pad = buf.len() - len;
+    debug_assert!(pad > 0 && pad <= 255);
+    buf[len..].fill(pad as u8);
+}
+
+// The number of pad bytes ending the final decrypted "block", None when malformed
+//
+// Constant time in the contents of "block": every byte is checked against the count and the
+// result is branched on once, so the time doesn't tell a wrong pad byte from a wrong count
+pub fn padcount(block: &[u8]) -> Option<usize> {
+    let padcount = *block.last()? as usize;
+
+    // Nonzero when the count is 0 or longer than the block, or a pad byte differs
+    let mut bad = ct_eq(padcount, 0) | ct_lt(block.len(), padcount);
+    for (i, &b) in block.iter().enumerate() {
+        let in_pad = ct_lt(block.len() - 1 - i, padcount);
+        bad |= in_pad & !ct_eq(b as usize, padcount);
+    }
+
+    match bad {
+        0 => Some(padcount),
+        _ => None,
+    }
+}
+
+// All ones when a == b, 0 otherwise
+fn ct_eq(a: usize, b: usize) -> usize {
+    let d = a ^ b;
+    ((d | d.wrapping_neg()) >> (usize::BITS - 1)).wrapping_sub(1)
+}
+
+// All ones when a < b, 0 otherwise, for a and b below 2^(usize::BITS - 1)
+fn ct_lt(a: usize, b: usize) -> usize {
+    (a.wrapping_sub(b) >> (usize::BITS - 1)).wrapping_neg()
+}
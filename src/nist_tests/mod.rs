@@ -1,5 +1,6 @@
-use super::*;
 use crate::libaes::{aes_decrypt, aes_encrypt};
+use crate::{Cipher, Key};
+use std::error::Error;
 
 mod vectors;
 use crate::nist_tests::vectors::*;
@@ -13,10 +14,9 @@ fn test_128_ecb_encrypt() -> Result<(), Box<dyn Error>> {
         let input = hex::decode(ECB128_ENCRYPT[i + 1])?;
         let expected = hex::decode(ECB128_ENCRYPT[i + 2])?;
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32[..16].copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_encrypt(128, &key32, &input, &Cipher::ECB, &ivector);
+        let computed = aes_encrypt(&key, &input, &Cipher::ECB, &ivector);
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -40,10 +40,9 @@ fn test_128_cbc_encrypt() -> Result<(), Box<dyn Error>> {
         let mut ivector: [u8; 16] = [0; 16];
         ivector.copy_from_slice(&iv);
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32[..16].copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_encrypt(128, &key32, &input, &Cipher::CBC, &ivector);
+        let computed = aes_encrypt(&key, &input, &Cipher::CBC, &ivector);
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -66,10 +65,9 @@ fn test_128_ecb_decrypt() -> Result<(), Box<dyn Error>> {
         let input = hex::decode(ECB128_DECRYPT[i + 1])?;
         let expected = hex::decode(ECB128_DECRYPT[i + 2])?;
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32[..16].copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_decrypt(128, &key32, &input, &Cipher::ECB, &ivector);
+        let computed = aes_decrypt(&key, &input, &Cipher::ECB, &ivector)?;
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -93,10 +91,9 @@ fn test_128_cbc_decrypt() -> Result<(), Box<dyn Error>> {
         let mut ivector: [u8; 16] = [0; 16];
         ivector.copy_from_slice(&iv);
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32[..16].copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_decrypt(128, &key32, &input, &Cipher::CBC, &ivector);
+        let computed = aes_decrypt(&key, &input, &Cipher::CBC, &ivector)?;
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -119,10 +116,9 @@ fn test_192_ecb_encrypt() -> Result<(), Box<dyn Error>> {
         let input = hex::decode(ECB192_ENCRYPT[i + 1])?;
         let expected = hex::decode(ECB192_ENCRYPT[i + 2])?;
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32[..24].copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_encrypt(192, &key32, &input, &Cipher::ECB, &ivector);
+        let computed = aes_encrypt(&key, &input, &Cipher::ECB, &ivector);
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -146,10 +142,9 @@ fn test_192_cbc_encrypt() -> Result<(), Box<dyn Error>> {
         let mut ivector: [u8; 16] = [0; 16];
         ivector.copy_from_slice(&iv);
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32[..24].copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_encrypt(192, &key32, &input, &Cipher::CBC, &ivector);
+        let computed = aes_encrypt(&key, &input, &Cipher::CBC, &ivector);
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -172,10 +167,9 @@ fn test_192_ecb_decrypt() -> Result<(), Box<dyn Error>> {
         let input = hex::decode(ECB192_DECRYPT[i + 1])?;
         let expected = hex::decode(ECB192_DECRYPT[i + 2])?;
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32[..24].copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_decrypt(192, &key32, &input, &Cipher::ECB, &ivector);
+        let computed = aes_decrypt(&key, &input, &Cipher::ECB, &ivector)?;
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -199,10 +193,9 @@ fn test_192_cbc_decrypt() -> Result<(), Box<dyn Error>> {
         let mut ivector: [u8; 16] = [0; 16];
         ivector.copy_from_slice(&iv);
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32[..24].copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_decrypt(192, &key32, &input, &Cipher::CBC, &ivector);
+        let computed = aes_decrypt(&key, &input, &Cipher::CBC, &ivector)?;
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -225,10 +218,9 @@ fn test_256_ecb_encrypt() -> Result<(), Box<dyn Error>> {
         let input = hex::decode(ECB256_ENCRYPT[i + 1])?;
         let expected = hex::decode(ECB256_ENCRYPT[i + 2])?;
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32.copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_encrypt(256, &key32, &input, &Cipher::ECB, &ivector);
+        let computed = aes_encrypt(&key, &input, &Cipher::ECB, &ivector);
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -252,10 +244,9 @@ fn test_256_cbc_encrypt() -> Result<(), Box<dyn Error>> {
         let mut ivector: [u8; 16] = [0; 16];
         ivector.copy_from_slice(&iv);
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32.copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_encrypt(256, &key32, &input, &Cipher::CBC, &ivector);
+        let computed = aes_encrypt(&key, &input, &Cipher::CBC, &ivector);
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -278,10 +269,9 @@ fn test_256_ecb_decrypt() -> Result<(), Box<dyn Error>> {
         let input = hex::decode(ECB256_DECRYPT[i + 1])?;
         let expected = hex::decode(ECB256_DECRYPT[i + 2])?;
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32.copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_decrypt(256, &key32, &input, &Cipher::ECB, &ivector);
+        let computed = aes_decrypt(&key, &input, &Cipher::ECB, &ivector)?;
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -305,10 +295,9 @@ fn test_256_cbc_decrypt() -> Result<(), Box<dyn Error>> {
         let mut ivector: [u8; 16] = [0; 16];
         ivector.copy_from_slice(&iv);
 
-        let mut key32: [u8; 32] = [0; 32];
-        key32.copy_from_slice(&key);
+        let key = Key::try_from(&key[..])?;
 
-        let computed = aes_decrypt(256, &key32, &input, &Cipher::CBC, &ivector);
+        let computed = aes_decrypt(&key, &input, &Cipher::CBC, &ivector)?;
         assert_eq!(expected, computed[..16]);
 
         println!(
@@ -4,7 +4,7 @@
 //
 
 #[rustfmt::skip]
-pub const ECB128_ENCRYPT: [&str; 852] = [
+pub static ECB128_ENCRYPT: [&str; 852] = [
     //          ** KEY **                         ** CLEARTEXT **               ** EXPECTED CIPHERTEXT **
     "80000000000000000000000000000000", "00000000000000000000000000000000", "0edd33d3c621e546455bd8ba1418bec8",
     "c0000000000000000000000000000000", "00000000000000000000000000000000", "4bc3f883450c113c64ca42e1112a9e87",
@@ -293,7 +293,7 @@ pub const ECB128_ENCRYPT: [&str; 852] = [
 ];
 
 #[rustfmt::skip]
-pub const ECB128_DECRYPT: [&str; 852] = [
+pub static ECB128_DECRYPT: [&str; 852] = [
     //          ** KEY **                         ** CLEARTEXT **               ** EXPECTED CIPHERTEXT **
     "00000000000000000000000000000000", "3ad78e726c1ec02b7ebfe92b23d9ec34", "80000000000000000000000000000000",
     "00000000000000000000000000000000", "aae5939c8efdf2f04e60b9fe7117b2c2", "c0000000000000000000000000000000",
@@ -582,7 +582,7 @@ pub const ECB128_DECRYPT: [&str; 852] = [
 ];
 
 #[rustfmt::skip]
-pub const CBC128_ENCRYPT: [&str; 2272] = [
+pub static CBC128_ENCRYPT: [&str; 2272] = [
     //          ** KEY **                         ** CLEARTEXT **               ** EXPECTED CIPHERTEXT **          ** INITIALIZATION VECTOR **
     "00000000000000000000000000000000", "f34481ec3cc627bacd5dc3fb08f273e6", "0336763e966d92595a567cc9ce537f5e", "00000000000000000000000000000000",
     "00000000000000000000000000000000", "9798c4640bad75c7c3227db910174e72", "a9a1631bf4996954ebc093957b234589", "00000000000000000000000000000000",
@@ -1155,7 +1155,7 @@ pub const CBC128_ENCRYPT: [&str; 2272] = [
 ];
 
 #[rustfmt::skip]
-pub const CBC128_DECRYPT: [&str; 2272] = [
+pub static CBC128_DECRYPT: [&str; 2272] = [
     //          ** KEY **                         ** CLEARTEXT **               ** EXPECTED CIPHERTEXT **          ** INITIALIZATION VECTOR **
     "00000000000000000000000000000000", "0336763e966d92595a567cc9ce537f5e", "f34481ec3cc627bacd5dc3fb08f273e6", "00000000000000000000000000000000",
     "00000000000000000000000000000000", "a9a1631bf4996954ebc093957b234589", "9798c4640bad75c7c3227db910174e72", "00000000000000000000000000000000",
@@ -1728,7 +1728,7 @@ pub const CBC128_DECRYPT: [&str; 2272] = [
 ];
 
 #[rustfmt::skip]
-pub const ECB192_ENCRYPT: [&str; 1050] = [
+pub static ECB192_ENCRYPT: [&str; 1050] = [
     //                ** KEY **                                  ** CLEARTEXT **                 ** EXPECTED CIPHERTEXT **
     "000000000000000000000000000000000000000000000000", "80000000000000000000000000000000", "6cd02513e8d4dc986b4afe087a60bd0c",
     "000000000000000000000000000000000000000000000000", "c0000000000000000000000000000000", "2ce1f8b7e30627c1c4519eada44bc436",
@@ -2083,7 +2083,7 @@ pub const ECB192_ENCRYPT: [&str; 1050] = [
 ];
 
 #[rustfmt::skip]
-pub const ECB192_DECRYPT: [&str; 1050] = [
+pub static ECB192_DECRYPT: [&str; 1050] = [
     //                ** KEY **                                  ** CLEARTEXT **                 ** EXPECTED CIPHERTEXT **
     "000000000000000000000000000000000000000000000000", "6cd02513e8d4dc986b4afe087a60bd0c", "80000000000000000000000000000000",
     "000000000000000000000000000000000000000000000000", "2ce1f8b7e30627c1c4519eada44bc436", "c0000000000000000000000000000000",
@@ -2438,7 +2438,7 @@ pub const ECB192_DECRYPT: [&str; 1050] = [
 ];
 
 #[rustfmt::skip]
-pub const CBC192_ENCRYPT: [&str; 2800] = [
+pub static CBC192_ENCRYPT: [&str; 2800] = [
     //                ** KEY **                                  ** CLEARTEXT **                 ** EXPECTED CIPHERTEXT **         ** INITIALIZATION VECTOR **
     "000000000000000000000000000000000000000000000000", "1b077a6af4b7f98229de786d7516b639", "275cfc0413d8ccb70513c3859b1d0f72", "00000000000000000000000000000000",
     "000000000000000000000000000000000000000000000000", "9c2d8842e5f48f57648205d39a239af1", "c9b8135ff1b5adc413dfd053b21bd96d", "00000000000000000000000000000000",
@@ -3143,7 +3143,7 @@ pub const CBC192_ENCRYPT: [&str; 2800] = [
 ];
 
 #[rustfmt::skip]
-pub const CBC192_DECRYPT: [&str; 2800] = [
+pub static CBC192_DECRYPT: [&str; 2800] = [
     //                ** KEY **                                  ** CLEARTEXT **                 ** EXPECTED CIPHERTEXT **         ** INITIALIZATION VECTOR **
     "000000000000000000000000000000000000000000000000", "275cfc0413d8ccb70513c3859b1d0f72", "1b077a6af4b7f98229de786d7516b639", "00000000000000000000000000000000",
     "000000000000000000000000000000000000000000000000", "c9b8135ff1b5adc413dfd053b21bd96d", "9c2d8842e5f48f57648205d39a239af1", "00000000000000000000000000000000",
@@ -3848,7 +3848,7 @@ pub const CBC192_DECRYPT: [&str; 2800] = [
 ];
 
 #[rustfmt::skip]
-pub const ECB256_ENCRYPT: [&str; 1215] = [
+pub static ECB256_ENCRYPT: [&str; 1215] = [
     //                        ** KEY **                                          ** CLEARTEXT **                 ** EXPECTED CIPHERTEXT **
     "0000000000000000000000000000000000000000000000000000000000000000", "80000000000000000000000000000000", "ddc6bf790c15760d8d9aeb6f9a75fd4e",
     "0000000000000000000000000000000000000000000000000000000000000000", "c0000000000000000000000000000000", "0a6bdc6d4c1e6280301fd8e97ddbe601",
@@ -4258,7 +4258,7 @@ pub const ECB256_ENCRYPT: [&str; 1215] = [
 ];
 
 #[rustfmt::skip]
-pub const ECB256_DECRYPT: [&str; 1215] = [
+pub static ECB256_DECRYPT: [&str; 1215] = [
     //                        ** KEY **                                          ** CLEARTEXT **                 ** EXPECTED CIPHERTEXT **
     "0000000000000000000000000000000000000000000000000000000000000000", "ddc6bf790c15760d8d9aeb6f9a75fd4e", "80000000000000000000000000000000",
     "0000000000000000000000000000000000000000000000000000000000000000", "0a6bdc6d4c1e6280301fd8e97ddbe601", "c0000000000000000000000000000000",
@@ -4668,7 +4668,7 @@ pub const ECB256_DECRYPT: [&str; 1215] = [
 ];
 
 #[rustfmt::skip]
-pub const CBC256_ENCRYPT: [&str; 3240] = [
+pub static CBC256_ENCRYPT: [&str; 3240] = [
     //                        ** KEY **                                          ** CLEARTEXT **                 ** EXPECTED CIPHERTEXT **         ** INITIALIZATION VECTOR **
     "0000000000000000000000000000000000000000000000000000000000000000", "014730f80ac625fe84f026c60bfd547d", "5c9d844ed46f9885085e5d6a4f94c7d7", "00000000000000000000000000000000",
     "0000000000000000000000000000000000000000000000000000000000000000", "0b24af36193ce4665f2825d7b4749c98", "a9ff75bd7cf6613d3731c77c3b6d0c04", "00000000000000000000000000000000",
@@ -5483,7 +5483,7 @@ pub const CBC256_ENCRYPT: [&str; 3240] = [
 ];
 
 #[rustfmt::skip]
-pub const CBC256_DECRYPT: [&str; 3240] = [
+pub static CBC256_DECRYPT: [&str; 3240] = [
     //                        ** KEY **                                          ** CLEARTEXT **                 ** EXPECTED CIPHERTEXT **         ** INITIALIZATION VECTOR **
     "0000000000000000000000000000000000000000000000000000000000000000", "5c9d844ed46f9885085e5d6a4f94c7d7", "014730f80ac625fe84f026c60bfd547d", "00000000000000000000000000000000",
     "0000000000000000000000000000000000000000000000000000000000000000", "a9ff75bd7cf6613d3731c77c3b6d0c04", "0b24af36193ce4665f2825d7b4749c98", "00000000000000000000000000000000",
@@ -0,0 +1,118 @@
+use crate::key::Key as AesKey;
+use crate::libaes::KeySchedule;
+use cipher::consts::{U16, U24, U32, U4};
+use cipher::inout::InOut;
+use cipher::{
+    AlgorithmName, Block, BlockBackend, BlockCipher, BlockClosure, BlockDecrypt, BlockEncrypt, BlockSizeUser, Key,
+    KeyInit, KeySizeUser, ParBlocks, ParBlocksSizeUser,
+};
+use core::fmt;
+
+// RustCrypto cipher traits (cipher 0.4) over KeySchedule
+//
+// Aes128, Aes192 and Aes256 plug into the generic mode crates, e.g. cbc::Encryptor<Aes128>,
+// ctr::Ctr64BE<Aes256> or aes_gcm::AesGcm<Aes128, U12>.  Parallel blocks are handed to the
+// LANES-wide pipeline.
+
+// Encrypt or decrypt with the key schedule, 4 blocks at a time when available
+struct Backend<'a> {
+    key: &'a KeySchedule,
+    encrypt: bool,
+}
+
+impl BlockSizeUser for Backend<'_> {
+    type BlockSize = U16;
+}
+
+impl ParBlocksSizeUser for Backend<'_> {
+    type ParBlocksSize = U4;
+}
+
+impl BlockBackend for Backend<'_> {
+    fn proc_block(&mut self, mut block: InOut<'_, '_, Block<Self>>) {
+        let mut buf: [u8; 16] = (*block.get_in()).into();
+        match self.encrypt {
+            true => self.key.encrypt_block(&mut buf),
+            false => self.key.decrypt_block(&mut buf),
+        }
+        block.get_out().copy_from_slice(&buf);
+    }
+
+    fn proc_par_blocks(&mut self, mut blocks: InOut<'_, '_, ParBlocks<Self>>) {
+        let mut buf = [0u8; 64];
+        for (chunk, block) in buf.chunks_exact_mut(16).zip(blocks.get_in().iter()) {
+            chunk.copy_from_slice(block);
+        }
+        match self.encrypt {
+            true => self.key.encrypt_blocks(&mut buf),
+            false => self.key.decrypt_blocks(&mut buf),
+        }
+        for (block, chunk) in blocks.get_out().iter_mut().zip(buf.chunks_exact(16)) {
+            block.copy_from_slice(chunk);
+        }
+    }
+}
+
+macro_rules! aes_type {
+    ($name:ident, $bits:literal, $keysize:ty) => {
+        #[doc = concat!("AES-", $bits, " block cipher")]
+        #[derive(Clone)]
+        pub struct $name {
+            key: KeySchedule,
+        }
+
+        impl KeySizeUser for $name {
+            type KeySize = $keysize;
+        }
+
+        impl KeyInit for $name {
+            fn new(key: &Key<Self>) -> Self {
+                let key = AesKey::try_from(key.as_slice()).expect("key size fixed by the type");
+                Self {
+                    key: KeySchedule::new(&key),
+                }
+            }
+        }
+
+        impl BlockSizeUser for $name {
+            type BlockSize = U16;
+        }
+
+        impl BlockCipher for $name {}
+
+        impl BlockEncrypt for $name {
+            fn encrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+                f.call(&mut Backend {
+                    key: &self.key,
+                    encrypt: true,
+                });
+            }
+        }
+
+        impl BlockDecrypt for $name {
+            fn decrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+                f.call(&mut Backend {
+                    key: &self.key,
+                    encrypt: false,
+                });
+            }
+        }
+
+        impl AlgorithmName for $name {
+            fn write_alg_name(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(stringify!($name))
+            }
+        }
+
+        // Round keys stay out of Debug output
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(concat!(stringify!($name), " { .. }"))
+            }
+        }
+    };
+}
+
+aes_type!(Aes128, 128, U16);
+aes_type!(Aes192, 192, U24);
+aes_type!(Aes256, 256, U32);
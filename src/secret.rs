@@ -0,0 +1,124 @@
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+use std::alloc::{self, Layout};
+use zeroize::Zeroize;
+
+// Fixed-size heap buffer for passwords and key material
+//
+// The pages are mlock'ed so they aren't swapped to disk, wiped and unlocked on drop.
+// Locking is best effort, RLIMIT_MEMLOCK may refuse it, see is_locked()
+//
+// Locks aren't counted, one munlock() unlocks a page for everything on it, so each buffer
+// is page aligned and rounded up to whole pages of its own
+pub struct SecretBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout, // of the pages, none for an empty buffer
+    locked: bool,
+}
+
+// Owns its pages like a Box<[u8]>
+unsafe impl Send for SecretBuf {}
+unsafe impl Sync for SecretBuf {}
+
+impl SecretBuf {
+    // "len" zero bytes
+    pub fn new(len: usize) -> Self {
+        let page = page_size();
+        let layout = Layout::from_size_align(len.next_multiple_of(page), page).expect("buffer size");
+        let ptr = match len {
+            0 => NonNull::dangling(),
+            // Safety: the layout isn't zero-sized
+            _ => NonNull::new(unsafe { alloc::alloc_zeroed(layout) })
+                .unwrap_or_else(|| alloc::handle_alloc_error(layout)),
+        };
+        let mut secret = Self {
+            ptr,
+            len,
+            layout,
+            locked: false,
+        };
+        secret.locked = len > 0 && lock(secret.pages());
+        secret
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        let mut secret = Self::new(bytes.len());
+        secret.copy_from_slice(bytes);
+        secret
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    // Every byte allocated, the buffer and the rest of its last page
+    fn pages(&mut self) -> &mut [u8] {
+        // Safety: "layout" bytes are allocated at "ptr", and none for an empty buffer
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn page_size() -> usize {
+    use nix::unistd::{sysconf, SysconfVar};
+
+    match sysconf(SysconfVar::PAGE_SIZE) {
+        Ok(Some(size)) if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+fn page_size() -> usize {
+    4096
+}
+
+#[cfg(target_family = "unix")]
+fn lock(pages: &[u8]) -> bool {
+    unsafe { nix::sys::mman::mlock(pages.as_ptr().cast(), pages.len()) }.is_ok()
+}
+
+#[cfg(not(target_family = "unix"))]
+fn lock(_pages: &[u8]) -> bool {
+    false
+}
+
+impl Deref for SecretBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safety: the first "len" bytes of the pages
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for SecretBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let len = self.len;
+        &mut self.pages()[..len]
+    }
+}
+
+impl Drop for SecretBuf {
+    fn drop(&mut self) {
+        self.pages().zeroize();
+        #[cfg(target_family = "unix")]
+        if self.locked {
+            let pages = self.pages();
+            let _ = unsafe { nix::sys::mman::munlock(pages.as_ptr().cast(), pages.len()) };
+        }
+        if self.len > 0 {
+            // Safety: allocated in new() with "layout"
+            unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+        }
+    }
+}
+
+// Never shows the contents
+impl fmt::Debug for SecretBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBuf({} bytes)", self.len)
+    }
+}
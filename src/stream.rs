@@ -0,0 +1,268 @@
+use crate::error::AesError;
+use crate::key::Key;
+use crate::libaes::KeySchedule;
+use crate::modes::{cbc, ctr, ecb, pkcs7};
+use crate::Cipher;
+use alloc::{vec, vec::Vec};
+use zeroize::{Zeroize, Zeroizing};
+
+// Streaming encryption and decryption
+//
+// Data is fed in arbitrary sized pieces with update() and completed with finalize().
+// Whole blocks are processed as soon as they arrive, a partial block is buffered until
+// the next update().  CBC carries the previous ciphertext block between calls and CTR
+// carries the counter and the position within the current keystream block.
+//
+// Decryption of ECB/CBC always holds back the last block, it is only known to be the
+// final (padded) block at finalize() where the PKCS#7 padding is validated and removed.
+//
+// nopkcs(true) matches the CLI's --nopkcs: no full pad block is added when encrypting
+// a multiple of 16 bytes and the padding is left in place when decrypting.
+
+// Counter mode state: 8-byte nonce, 64-bit big-endian counter and unused keystream bytes
+#[derive(Clone)]
+struct Ctr {
+    nonce: [u8; 8],
+    counter: u64,
+    keystream: [u8; 16],
+    used: usize, // bytes of keystream consumed, 16 when exhausted
+}
+
+impl Ctr {
+    fn new(iv: &[u8; 16]) -> Self {
+        Self {
+            nonce: iv[..8].try_into().expect("8-byte nonce"),
+            counter: u64::from_be_bytes(iv[8..].try_into().expect("from_be_bytes")),
+            keystream: [0; 16],
+            used: 16,
+        }
+    }
+
+    // The counter block for the next unused keystream block
+    fn block(&self) -> [u8; 16] {
+        let mut block = [0u8; 16];
+        block[..8].copy_from_slice(&self.nonce);
+        block[8..].copy_from_slice(&self.counter.to_be_bytes());
+        block
+    }
+
+    fn apply(&mut self, key: &KeySchedule, buf: &mut [u8]) {
+        // Finish the keystream block left over from the previous call
+        let n = buf.len().min(16 - self.used);
+        for (b, k) in buf[..n].iter_mut().zip(&self.keystream[self.used..]) {
+            *b ^= k;
+        }
+        self.used += n;
+        let buf = &mut buf[n..];
+
+        // Whole blocks
+        let whole = 16 * (buf.len() / 16);
+        self.counter = ctr::apply(key, &mut buf[..whole], &self.block());
+
+        // Keep the unused keystream of a trailing partial block
+        let rest = &mut buf[whole..];
+        if !rest.is_empty() {
+            self.keystream = self.block();
+            key.encrypt_block(&mut self.keystream);
+            self.counter = self.counter.wrapping_add(1);
+            for (b, k) in rest.iter_mut().zip(self.keystream) {
+                *b ^= k;
+            }
+            self.used = rest.len();
+        }
+    }
+}
+
+// State shared by Encryptor and Decryptor, the buffered bytes and keystream are wiped on drop
+#[derive(Clone)]
+struct Core {
+    key: KeySchedule,
+    cipher: Cipher,
+    chain: [u8; 16], // CBC: previous ciphertext block
+    ctr: Ctr,
+    pending: [u8; 16], // ECB/CBC: bytes not yet processed
+    npending: usize,
+    total: usize, // bytes passed to update()
+    nopkcs: bool,
+}
+
+impl Drop for Core {
+    fn drop(&mut self) {
+        self.pending.zeroize();
+        self.ctr.keystream.zeroize();
+    }
+}
+
+impl Core {
+    fn new(key: &Key, cipher: Cipher, iv: &[u8; 16]) -> Self {
+        Self {
+            key: KeySchedule::new(key),
+            cipher,
+            chain: *iv,
+            ctr: Ctr::new(iv),
+            pending: [0; 16],
+            npending: 0,
+            total: 0,
+            nopkcs: false,
+        }
+    }
+
+    // Append "data" to "out" processing whole blocks and buffering the rest
+    //
+    // When "hold" is set at least one byte (a full block once complete) is kept back
+    fn update(&mut self, data: &[u8], out: &mut Vec<u8>, hold: bool, mode: fn(&mut Self, &mut [u8])) {
+        let start = out.len();
+        self.total += data.len();
+
+        if self.cipher == Cipher::CTR {
+            out.extend_from_slice(data);
+            self.ctr.apply(&self.key, &mut out[start..]);
+            return;
+        }
+
+        let total = self.npending + data.len();
+        let nbytes = match hold {
+            true => 16 * (total.saturating_sub(1) / 16),
+            false => 16 * (total / 16),
+        };
+
+        if nbytes == 0 {
+            self.pending[self.npending..total].copy_from_slice(data);
+            self.npending = total;
+            return;
+        }
+
+        let from_data = nbytes - self.npending;
+        out.extend_from_slice(&self.pending[..self.npending]);
+        out.extend_from_slice(&data[..from_data]);
+        mode(self, &mut out[start..]);
+
+        let rest = &data[from_data..];
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.npending = rest.len();
+    }
+
+    // Encrypt whole blocks, carrying the last ciphertext block for CBC
+    fn encrypt(&mut self, blocks: &mut [u8]) {
+        match self.cipher {
+            Cipher::ECB => ecb::encrypt(&self.key, blocks),
+            _ => cbc::encrypt(&self.key, blocks, &self.chain),
+        }
+        if let Some(last) = blocks.chunks_exact(16).last() {
+            self.chain.copy_from_slice(last);
+        }
+    }
+
+    // Decrypt whole blocks, carrying the last ciphertext block for CBC
+    fn decrypt(&mut self, blocks: &mut [u8]) {
+        let next = blocks
+            .chunks_exact(16)
+            .last()
+            .map(|b| <[u8; 16]>::try_from(b).expect("16-byte block"));
+        match self.cipher {
+            Cipher::ECB => ecb::decrypt(&self.key, blocks),
+            _ => cbc::decrypt(&self.key, blocks, &self.chain),
+        }
+        if let Some(next) = next {
+            self.chain = next;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Encryptor(Core);
+
+impl Encryptor {
+    pub fn new(key: &Key, cipher: Cipher, iv: &[u8; 16]) -> Self {
+        Self(Core::new(key, cipher, iv))
+    }
+
+    // Prevent a full pad block
+    pub fn nopkcs(mut self, nopkcs: bool) -> Self {
+        self.0.nopkcs = nopkcs;
+        self
+    }
+
+    pub fn update(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() + 16);
+        self.update_into(data, &mut out);
+        out
+    }
+
+    // Append the ciphertext available so far to "out"
+    pub fn update_into(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        self.0.update(data, out, false, Core::encrypt);
+    }
+
+    // PKCS#7 pad and encrypt the final block (ECB/CBC), CTR has nothing left to output
+    pub fn finalize(self) -> Vec<u8> {
+        let mut out = vec![];
+        self.finalize_into(&mut out);
+        out
+    }
+
+    pub fn finalize_into(mut self, out: &mut Vec<u8>) {
+        let core = &mut self.0;
+        if core.cipher == Cipher::CTR || (core.nopkcs && core.npending == 0) {
+            return;
+        }
+
+        let mut block = core.pending;
+        pkcs7::pad(&mut block, core.npending);
+        core.encrypt(&mut block);
+        out.extend_from_slice(&block);
+    }
+}
+
+#[derive(Clone)]
+pub struct Decryptor(Core);
+
+impl Decryptor {
+    pub fn new(key: &Key, cipher: Cipher, iv: &[u8; 16]) -> Self {
+        Self(Core::new(key, cipher, iv))
+    }
+
+    // Skip PKCS#7 pad removal
+    pub fn nopkcs(mut self, nopkcs: bool) -> Self {
+        self.0.nopkcs = nopkcs;
+        self
+    }
+
+    pub fn update(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len());
+        self.update_into(data, &mut out);
+        out
+    }
+
+    // Append the plaintext available so far to "out", the last block is held back
+    pub fn update_into(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        self.0.update(data, out, true, Core::decrypt);
+    }
+
+    // Decrypt the final block (ECB/CBC), validating and removing the PKCS#7 padding
+    pub fn finalize(self) -> Result<Vec<u8>, AesError> {
+        let mut out = vec![];
+        self.finalize_into(&mut out)?;
+        Ok(out)
+    }
+
+    pub fn finalize_into(mut self, out: &mut Vec<u8>) -> Result<(), AesError> {
+        let core = &mut self.0;
+        if core.cipher == Cipher::CTR || (core.nopkcs && core.npending == 0) {
+            return Ok(());
+        }
+
+        if core.npending != 16 {
+            return Err(AesError::InvalidCiphertextLength(core.total));
+        }
+
+        let mut block = Zeroizing::new(core.pending);
+        core.decrypt(&mut block[..]);
+        let padcount = match core.nopkcs {
+            true => 0,
+            false => pkcs7::padcount(&block[..]).ok_or(AesError::BadPadding)?,
+        };
+        out.extend_from_slice(&block[..16 - padcount]);
+        Ok(())
+    }
+}
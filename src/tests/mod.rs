@@ -1,25 +1,40 @@
-use super::*;
-use crate::general::{get_ivector, get_passkey32};
-use crate::libaes::aes_decrypt;
-use crate::{Cipher, Kdf};
+use crate::batch;
+use crate::block::BlockCipher;
+use crate::chunked::{self, ChunkedReader, ChunkedWriter};
+use crate::container::{self, Header, Mode};
+use crate::files;
+use crate::framing::Framing;
+use crate::general::{
+    disable_core_dumps, get_ivector, get_key, AtomicFile, DecodingReader, Encoding, EncodingWriter, Input,
+};
+use crate::io::{DecryptingReader, EncryptingWriter};
+use crate::kdf::Kdf;
+use crate::libaes::{aes_decrypt, aes_encrypt, aes_encrypt_in_place, KeySchedule};
+use crate::literal;
+use crate::modes::{cbc, ctr, ecb, gcm, pkcs7};
+use crate::secret::SecretBuf;
+use crate::stream::{Decryptor, Encryptor};
+use crate::{AesError, Cipher, Key, KeySize};
 use hex_literal::hex;
+use std::error::Error;
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
 #[test]
 fn test_pbkdf2() -> Result<(), Box<dyn Error>> {
     // 128
-    let (key, iv) = Kdf::PBKDF2(80_000).keyiv(128, b"Password", b"NaCl")?;
-    assert_eq!(&key[..16], hex!("4ddcd8f60b98be21830cee5ef22701f9"));
+    let (key, iv) = Kdf::PBKDF2(80_000).keyiv(KeySize::Aes128, b"Password", b"NaCl")?;
+    assert_eq!(key.as_bytes(), hex!("4ddcd8f60b98be21830cee5ef22701f9"));
     assert_eq!(iv, hex!("641a4418d04c0414aeff08876b34ab56"));
 
     // 192
-    let (key, iv) = Kdf::PBKDF2(80_000).keyiv(192, b"Password", b"NaCl")?;
-    assert_eq!(&key[..24], hex!("4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414"));
+    let (key, iv) = Kdf::PBKDF2(80_000).keyiv(KeySize::Aes192, b"Password", b"NaCl")?;
+    assert_eq!(key.as_bytes(), hex!("4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414"));
     assert_eq!(iv, hex!("aeff08876b34ab56a1d425a122583354"));
 
     // 256
-    let (key, iv) = Kdf::PBKDF2(80_000).keyiv(256, b"Password", b"NaCl")?;
+    let (key, iv) = Kdf::PBKDF2(80_000).keyiv(KeySize::Aes256, b"Password", b"NaCl")?;
     assert_eq!(
-        key,
+        key.as_bytes(),
         hex!("4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56")
     );
     assert_eq!(iv, hex!("a1d425a1225833549adb841b51c9b317"));
@@ -30,19 +45,19 @@ fn test_pbkdf2() -> Result<(), Box<dyn Error>> {
 #[test]
 fn test_argon2() -> Result<(), Box<dyn Error>> {
     // 128
-    let (key, iv) = Kdf::ARGON2.keyiv(128, b"Password", b"saltydog")?;
-    assert_eq!(&key[..16], hex!("e997d0e202dd32d6fa8379dfcf65e337"));
+    let (key, iv) = Kdf::ARGON2.keyiv(KeySize::Aes128, b"Password", b"saltydog")?;
+    assert_eq!(key.as_bytes(), hex!("e997d0e202dd32d6fa8379dfcf65e337"));
     assert_eq!(iv, hex!("7dd5ae2a37c62f11b04f5aacc02fccb0"));
 
     // 192
-    let (key, iv) = Kdf::ARGON2.keyiv(192, b"Password", b"saltydog")?;
-    assert_eq!(&key[..24], hex!("e997d0e202dd32d6fa8379dfcf65e3377dd5ae2a37c62f11"));
+    let (key, iv) = Kdf::ARGON2.keyiv(KeySize::Aes192, b"Password", b"saltydog")?;
+    assert_eq!(key.as_bytes(), hex!("e997d0e202dd32d6fa8379dfcf65e3377dd5ae2a37c62f11"));
     assert_eq!(iv, hex!("b04f5aacc02fccb080b5df4bbe34cbfc"));
 
     // 256
-    let (key, iv) = Kdf::ARGON2.keyiv(256, b"Password", b"saltydog")?;
+    let (key, iv) = Kdf::ARGON2.keyiv(KeySize::Aes256, b"Password", b"saltydog")?;
     assert_eq!(
-        key,
+        key.as_bytes(),
         hex!("e997d0e202dd32d6fa8379dfcf65e3377dd5ae2a37c62f11b04f5aacc02fccb0")
     );
     assert_eq!(iv, hex!("80b5df4bbe34cbfc000245ab198ad87f"));
@@ -77,128 +92,1348 @@ fn test_get_ivector_panic() {
 }
 
 #[test]
-fn test_get_passkey() -> Result<(), Box<dyn Error>> {
+fn test_get_key() -> Result<(), Box<dyn Error>> {
     let quiet = true;
 
-    // pub fn get_passkey32(
-    //     bits: Option<usize>,
+    // pub fn get_key(
+    //     size: Option<KeySize>,
     //     key: Option<&String>,
     //     hexkey: Option<&String>,
-    //     quiet: bool) > Result<(usize, [u8; 32]), Box<dyn Error>> { ... }
+    //     quiet: bool) -> Result<Key, AesError> { ... }
 
     // 128 - derived
     let hexkey = "abcdef".to_string();
-    let (bits, passkey) = get_passkey32(None, None, Some(&hexkey), quiet)?;
-    assert!(bits == 128 && hexkey.len() < 32);
-    assert_eq!(
-        passkey,
-        hex!("abcdef0000000000000000000000000000000000000000000000000000000000")
-    );
+    let passkey = get_key(None, None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes128 && hexkey.len() < 32);
+    assert_eq!(passkey.as_bytes(), hex!("abcdef00000000000000000000000000"));
 
     let hexkey = "abcdef0123456789ABCDEF0123456789".to_string();
-    let (bits, passkey) = get_passkey32(None, None, Some(&hexkey), quiet)?;
-    assert!(bits == 128 && hexkey.len() == 32);
-    assert_eq!(
-        passkey,
-        hex!("ABCDEF0123456789ABCDEF012345678900000000000000000000000000000000")
-    );
+    let passkey = get_key(None, None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes128 && hexkey.len() == 32);
+    assert_eq!(passkey.as_bytes(), hex!("ABCDEF0123456789ABCDEF0123456789"));
 
     // 192 - derived
     let hexkey = "abcdef0123456789ABCDEF0123456789a".to_string();
-    let (bits, passkey) = get_passkey32(None, None, Some(&hexkey), quiet)?;
-    assert!(bits == 192 && hexkey.len() > 32 && hexkey.len() <= 48);
+    let passkey = get_key(None, None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes192 && hexkey.len() > 32 && hexkey.len() <= 48);
     assert_eq!(
-        passkey,
-        hex!("ABCDEF0123456789ABCDEF0123456789A0000000000000000000000000000000")
+        passkey.as_bytes(),
+        hex!("ABCDEF0123456789ABCDEF0123456789A000000000000000")
     );
 
     let hexkey = "abcdef0123456789abcdef0123456789ABCdef0123456789".to_string();
-    let (bits, passkey) = get_passkey32(None, None, Some(&hexkey), quiet)?;
-    assert!(bits == 192 && hexkey.len() == 48);
+    let passkey = get_key(None, None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes192 && hexkey.len() == 48);
     assert_eq!(
-        passkey,
-        hex!("ABCDEF0123456789ABCDEF0123456789abcdef01234567890000000000000000")
+        passkey.as_bytes(),
+        hex!("ABCDEF0123456789ABCDEF0123456789abcdef0123456789")
     );
 
     // 256 - derived
     let hexkey = "abcdef0123456789ABCDEF0123456789abcdef0123456789abcdef0123456789".to_string();
-    let (bits, passkey) = get_passkey32(None, None, Some(&hexkey), quiet)?;
-    assert!(bits == 256 && hexkey.len() == 64);
+    let passkey = get_key(None, None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes256 && hexkey.len() == 64);
     assert_eq!(
-        passkey,
+        passkey.as_bytes(),
         hex!("ABCDEF0123456789ABCDEF0123456789abcdef0123456789abcdef0123456789")
     );
 
     let hexkey = "abcdef0123456789ABCDEF0123456789abcdef0123456789abcdef0123456789ab".to_string();
-    let (bits, passkey) = get_passkey32(None, None, Some(&hexkey), quiet)?;
-    assert!(bits == 256 && hexkey.len() > 64);
+    let passkey = get_key(None, None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes256 && hexkey.len() > 64);
     assert_eq!(
-        passkey,
+        passkey.as_bytes(),
         hex!("ABCDEF0123456789ABCDEF0123456789abcdef0123456789abcdef0123456789")
     );
 
     // 128 - explicit
     let hexkey = "abcdef".to_string();
-    let (bits, passkey) = get_passkey32(Some(128), None, Some(&hexkey), quiet)?;
-    assert!(bits == 128 && hexkey.len() < 32);
-    assert_eq!(
-        passkey,
-        hex!("ABCDEF0000000000000000000000000000000000000000000000000000000000")
-    );
+    let passkey = get_key(Some(KeySize::Aes128), None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes128 && hexkey.len() < 32);
+    assert_eq!(passkey.as_bytes(), hex!("ABCDEF00000000000000000000000000"));
 
     let hexkey = "abcdef0123456789ABCDEF0123456789abcdef0123456789abcdef0123456789".to_string();
-    let (bits, passkey) = get_passkey32(Some(128), None, Some(&hexkey), quiet)?;
-    assert!(bits == 128 && hexkey.len() > 32);
-    assert_eq!(
-        passkey,
-        hex!("ABCDEF0123456789abcdef012345678900000000000000000000000000000000")
-    );
+    let passkey = get_key(Some(KeySize::Aes128), None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes128 && hexkey.len() > 32);
+    assert_eq!(passkey.as_bytes(), hex!("ABCDEF0123456789abcdef0123456789"));
 
     // 192 - explicit
     let hexkey = "abcdef0123456789ABCDEF0123456789abcd".to_string();
-    let (bits, passkey) = get_passkey32(Some(192), None, Some(&hexkey), quiet)?;
-    assert!(bits == 192 && hexkey.len() < 48);
+    let passkey = get_key(Some(KeySize::Aes192), None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes192 && hexkey.len() < 48);
     assert_eq!(
-        passkey,
-        hex!("ABCDEF0123456789abcdef0123456789ABCD0000000000000000000000000000")
+        passkey.as_bytes(),
+        hex!("ABCDEF0123456789abcdef0123456789ABCD000000000000")
     );
 
     let hexkey = "abcdef0123456789ABCDEF0123456789abcdef0123456789abcdef0123456789".to_string();
-    let (bits, passkey) = get_passkey32(Some(192), None, Some(&hexkey), quiet)?;
-    assert!(bits == 192 && hexkey.len() > 48);
+    let passkey = get_key(Some(KeySize::Aes192), None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes192 && hexkey.len() > 48);
     assert_eq!(
-        passkey,
-        hex!("ABCDEF0123456789abcdef0123456789ABCDEF01234567890000000000000000")
+        passkey.as_bytes(),
+        hex!("ABCDEF0123456789abcdef0123456789ABCDEF0123456789")
     );
 
     // 256 - explicit
     let hexkey = "abcdef0123456789ABCDEF0123456789abcdef0123456789abcdef0123456789".to_string();
-    let (bits, passkey) = get_passkey32(Some(256), None, Some(&hexkey), quiet)?;
-    assert!(bits == 256 && hexkey.len() == 64);
+    let passkey = get_key(Some(KeySize::Aes256), None, Some(&hexkey), quiet)?;
+    assert!(passkey.size() == KeySize::Aes256 && hexkey.len() == 64);
     assert_eq!(
-        passkey,
+        passkey.as_bytes(),
         hex!("ABCDEF0123456789abcdef0123456789ABCDEF0123456789ABCDEF0123456789")
     );
 
     // 128 - derived - ascii key
     let key = "R&D".to_string();
-    let (bits, passkey) = get_passkey32(None, Some(&key), None, quiet)?;
-    assert!(bits == 128);
+    let passkey = get_key(None, Some(&key), None, quiet)?;
+    assert!(passkey.size() == KeySize::Aes128);
+    assert_eq!(passkey.as_bytes(), hex!("52264400000000000000000000000000"));
+
+    let key = "Allman Brothers!".to_string();
+    let passkey = get_key(None, Some(&key), None, quiet)?;
+    assert!(passkey.size() == KeySize::Aes128);
+    assert_eq!(passkey.as_bytes(), hex!("416C6C6D616E2042726F746865727321"));
+    Ok(())
+}
+
+#[test]
+fn test_key() {
+    for (size, bits, bytes) in [
+        (KeySize::Aes128, 128, 16),
+        (KeySize::Aes192, 192, 24),
+        (KeySize::Aes256, 256, 32),
+    ] {
+        assert_eq!(KeySize::from_bits(bits).unwrap(), size);
+        assert_eq!((size.bits(), size.bytes()), (bits, bytes));
+
+        let key = Key::new(size, &[9; 32][..bytes]).unwrap();
+        assert_eq!(key.size(), size);
+        assert_eq!(key.as_bytes(), &[9; 32][..bytes]);
+        assert_eq!(Key::try_from(&[9; 32][..bytes]).unwrap(), key);
+    }
+    assert!(matches!(KeySize::from_bits(100), Err(AesError::InvalidKeyLength(100))));
+
+    // Exactly size.bytes(), no padding or truncation
+    assert!(matches!(
+        Key::new(KeySize::Aes128, &[0; 15]),
+        Err(AesError::KeySizeMismatch {
+            expected: 128,
+            bits: 120
+        })
+    ));
+    let e = Key::new(KeySize::Aes192, &[0; 32]).unwrap_err();
+    assert!(matches!(
+        e,
+        AesError::KeySizeMismatch {
+            expected: 192,
+            bits: 256
+        }
+    ));
+    assert_eq!(e.to_string(), "256-bit key, expected 192");
+    assert!(Key::try_from(&[0u8; 20][..]).is_err());
+
+    // Debug never shows the key bytes
+    assert_eq!(format!("{:?}", Key::from([0xab; 24])), "Key(Aes192)");
+}
+
+#[test]
+fn test_zeroize() {
+    use std::mem::{size_of, ManuallyDrop};
+    use zeroize::{Zeroize, ZeroizeOnDrop};
+
+    // The bytes of "value" after its destructor has run
+    fn bytes_after_drop<T>(value: T) -> Vec<u8> {
+        let mut value = ManuallyDrop::new(value);
+        unsafe {
+            ManuallyDrop::drop(&mut value);
+            std::slice::from_raw_parts(&value as *const ManuallyDrop<T> as *const u8, size_of::<T>()).to_vec()
+        }
+    }
+    fn zeroize_on_drop<T: ZeroizeOnDrop>() {}
+    zeroize_on_drop::<Key>();
+    zeroize_on_drop::<KeySchedule>();
+
+    let mut key = Key::from([0xab; 24]);
+    key.zeroize();
+    assert_eq!(key.as_bytes(), [0; 24]);
+    assert_eq!(key.size(), KeySize::Aes192);
+
+    // Only the Aes256 discriminant is left
+    assert!(!bytes_after_drop(Key::from([0xab; 32])).contains(&0xab));
+
+    // Round keys and the number of rounds
+    let key = KeySchedule::new(&Key::from([0xab; 32]));
+    assert!(bytes_after_drop(key).iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_secret_buf() {
+    let mut secret = SecretBuf::new(48);
+    assert_eq!(&secret[..], [0; 48]);
+    secret[..4].copy_from_slice(b"key!");
+    assert_eq!(&secret[..4], b"key!");
+
+    let secret = SecretBuf::from_slice(b"password");
+    assert_eq!(&secret[..], b"password");
+    assert_eq!(format!("{secret:?}"), "SecretBuf(8 bytes)");
+
+    // Nothing to lock
+    assert!(!SecretBuf::new(0).is_locked());
+
+    // Each on pages of its own, dropping one doesn't munlock() the other
+    #[cfg(target_family = "unix")]
+    {
+        use nix::unistd::{sysconf, SysconfVar};
+
+        let page = sysconf(SysconfVar::PAGE_SIZE).unwrap().unwrap() as usize;
+        let secrets = [SecretBuf::new(8), SecretBuf::new(8), SecretBuf::new(page + 1)];
+        for secret in &secrets {
+            assert_eq!(secret.as_ptr() as usize % page, 0);
+        }
+        assert!((secrets[0].as_ptr() as usize).abs_diff(secrets[1].as_ptr() as usize) >= page);
+        assert_eq!(secrets[2].len(), page + 1);
+    }
+}
+
+#[test]
+fn test_disable_core_dumps() {
+    // Process-wide, so it's done in a child running only this test
+    if std::env::var_os("AES_TEST_CORE_DUMPS").is_none() {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "tests::test_disable_core_dumps"])
+            .env("AES_TEST_CORE_DUMPS", "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("1 passed"));
+        return;
+    }
+
+    disable_core_dumps().unwrap();
+    #[cfg(target_os = "linux")]
+    assert!(!nix::sys::prctl::get_dumpable().unwrap());
+    #[cfg(target_family = "unix")]
     assert_eq!(
-        passkey,
-        hex!("5226440000000000000000000000000000000000000000000000000000000000")
+        nix::sys::resource::getrlimit(nix::sys::resource::Resource::RLIMIT_CORE).unwrap(),
+        (0, 0)
     );
+}
 
-    let key = "Allman Brothers!".to_string();
-    let (bits, passkey) = get_passkey32(None, Some(&key), None, quiet)?;
-    assert!(bits == 128);
+#[test]
+fn test_key_schedule() {
+    // FIPS-197 Appendix C, example vectors for each key size
+    let plaintext = hex!("00112233445566778899aabbccddeeff");
+    let passkey = hex!("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+
+    for (size, expected) in [
+        (KeySize::Aes128, hex!("69c4e0d86a7b0430d8cdb78070b4c55a")),
+        (KeySize::Aes192, hex!("dda97ca4864cdfe06eaf70a0ec0d7191")),
+        (KeySize::Aes256, hex!("8ea2b7ca516745bfeafc49904b496089")),
+    ] {
+        let key = KeySchedule::new(&Key::new(size, &passkey[..size.bytes()]).unwrap());
+
+        let mut block = plaintext;
+        key.encrypt_block(&mut block);
+        assert_eq!(block, expected);
+
+        // equivalent inverse cipher
+        key.decrypt_block(&mut block);
+        assert_eq!(block, plaintext);
+    }
+}
+
+#[test]
+fn test_const_aes() {
+    // FIPS-197 Appendix C.3 evaluated at compile time
+    const KEY: &KeySchedule = &KeySchedule::from_bytes(&hex!(
+        "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"
+    ));
+    const BLOCK: [u8; 16] = {
+        let mut block = hex!("00112233445566778899aabbccddeeff");
+        KEY.encrypt_block(&mut block);
+        block
+    };
+    assert_eq!(BLOCK, hex!("8ea2b7ca516745bfeafc49904b496089"));
+
+    assert_eq!(crate::aes_literal!(b"0123456789abcdef", ""), "");
+    assert_eq!(crate::aes_literal!(b"0123456789abcdef", "short"), "short");
+    assert_eq!(
+        crate::aes_literal!(
+            b"0123456789abcdef01234567",
+            "https://config.example/v1?token=more-than-two-blocks"
+        ),
+        "https://config.example/v1?token=more-than-two-blocks"
+    );
+
+    // The same keystream as runtime CTR, the iv differs per call site
+    let key = *b"0123456789abcdef0123456789abcdef";
+    let text = "the quick brown fox jumps over the lazy dog";
+    let iv = literal::iv(file!(), line!(), column!());
+    let ciphertext = literal::encrypt::<43>(&KeySchedule::from_bytes(&key), &iv, text.as_bytes());
+    assert_eq!(
+        ciphertext[..],
+        aes_encrypt(&Key::from(key), text.as_bytes(), &Cipher::CTR, &iv)
+    );
+    assert_eq!(literal::decrypt(&key, &iv, &ciphertext), text);
+    assert_ne!(iv, literal::iv(file!(), line!(), column!()));
+}
+
+#[test]
+fn test_encrypt_blocks() {
+    // The interleaved pipeline must agree with one block at a time, 21 blocks covers
+    // several full runs of lanes plus a remainder
+    let key = KeySchedule::new(&Key::from(hex!(
+        "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4"
+    )));
+    let plaintext: Vec<u8> = (0..21 * 16).map(|n| (n * 7 % 251) as u8).collect();
+
+    let mut expected = plaintext.clone();
+    for block in expected.chunks_exact_mut(16) {
+        key.encrypt_block(block.try_into().unwrap());
+    }
+
+    let mut computed = plaintext.clone();
+    key.encrypt_blocks(&mut computed);
+    assert_eq!(computed, expected);
+
+    key.decrypt_blocks(&mut computed);
+    assert_eq!(computed, plaintext);
+}
+
+#[test]
+fn test_batch() -> Result<(), Box<dyn Error>> {
+    // Lengths 0..70 put 1 to 5 blocks side by side in the CBC lanes and across CTR counters
+    let data: Vec<u8> = (0..70).map(|n| (n * 13 % 256) as u8).collect();
+    let mut ivs: Vec<[u8; 16]> = (0..70u8).map(|n| [n; 16]).collect();
+    ivs[3] = [0xff; 16]; // the CTR counter wraps
+    let records: Vec<([u8; 16], &[u8])> = ivs.iter().enumerate().map(|(n, iv)| (*iv, &data[..n])).collect();
+
+    for size in [KeySize::Aes128, KeySize::Aes192, KeySize::Aes256] {
+        let key = Key::new(size, &data[..size.bytes()])?;
+        for cipher in [Cipher::ECB, Cipher::CBC, Cipher::CTR] {
+            let sealed = batch::aes_encrypt_batch(&key, &records, &cipher);
+            assert_eq!(sealed.len(), records.len());
+            for (ciphertext, (iv, plaintext)) in sealed.iter().zip(&records) {
+                assert_eq!(ciphertext, aes_encrypt(&key, plaintext, &cipher, iv));
+            }
+
+            let sealed: Vec<([u8; 16], &[u8])> = ivs.iter().copied().zip(sealed.iter()).collect();
+            let opened = batch::aes_decrypt_batch(&key, &sealed, &cipher)?;
+            for (plaintext, (iv, ciphertext)) in opened.iter().zip(&sealed) {
+                assert_eq!(plaintext, aes_decrypt(&key, ciphertext, &cipher, iv)?);
+            }
+        }
+    }
+
+    // Batches append to the arena
+    let key = KeySchedule::new(&Key::from([7u8; 16]));
+    let mut arena = batch::Arena::new();
+    for cipher in [Cipher::CTR, Cipher::CBC] {
+        arena.clear();
+        key.encrypt_batch(&records[..5], &cipher, &mut arena);
+        key.encrypt_batch(&records[5..], &cipher, &mut arena);
+        assert_eq!(
+            arena.as_bytes(),
+            batch::aes_encrypt_batch(&Key::from([7u8; 16]), &records, &cipher).as_bytes()
+        );
+    }
+    assert_eq!(
+        format!("{arena:?}"),
+        format!("Arena(70 records, {} bytes)", arena.as_bytes().len())
+    );
+    arena.clear();
+    assert!(arena.is_empty() && arena.get(0).is_none());
+
+    // Nothing is appended when a record isn't whole blocks
+    assert!(matches!(
+        key.decrypt_batch(&records[16..18], &Cipher::ECB, &mut arena),
+        Err(AesError::InvalidCiphertextLength(17))
+    ));
+    assert!(arena.is_empty());
+    Ok(())
+}
+
+// Feed "data" through update() in uneven pieces
+fn chunked(data: &[u8], mut update: impl FnMut(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    let mut out = vec![];
+    let mut rest = data;
+    for n in [1, 15, 16, 0, 17, 33, 5, 64, 100].iter().cycle() {
+        if rest.is_empty() {
+            break;
+        }
+        let (chunk, tail) = rest.split_at((*n).min(rest.len()));
+        out.extend(update(chunk));
+        rest = tail;
+    }
+    out
+}
+
+#[test]
+fn test_stream() -> Result<(), Box<dyn Error>> {
+    let passkey = hex!("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+    let iv = hex!("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+
+    for size in [KeySize::Aes128, KeySize::Aes192, KeySize::Aes256] {
+        let key = Key::new(size, &passkey[..size.bytes()])?;
+        let bits = size.bits();
+        for cipher in [Cipher::ECB, Cipher::CBC, Cipher::CTR] {
+            for len in [0, 1, 15, 16, 17, 255, 256, 1000] {
+                let plaintext: Vec<u8> = (0..len).map(|n| (n % 251) as u8).collect();
+                let expected = aes_encrypt(&key, &plaintext, &cipher, &iv);
+
+                let mut enc = Encryptor::new(&key, cipher, &iv);
+                let mut ciphertext = chunked(&plaintext, |chunk| enc.update(chunk));
+                ciphertext.extend(enc.finalize());
+                assert_eq!(ciphertext, expected, "encrypt {bits} {cipher:?} {len}");
+
+                let mut dec = Decryptor::new(&key, cipher, &iv);
+                let mut decrypted = chunked(&ciphertext, |chunk| dec.update(chunk));
+                decrypted.extend(dec.finalize()?);
+                assert_eq!(decrypted, plaintext, "decrypt {bits} {cipher:?} {len}");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_stream_bad_padding() {
+    let key = Key::from([0u8; 16]);
+    let iv = [0u8; 16];
+
+    // Not a multiple of the block size
+    let mut dec = Decryptor::new(&key, Cipher::CBC, &iv);
+    dec.update(&[0u8; 20]);
+    assert!(matches!(dec.finalize(), Err(AesError::InvalidCiphertextLength(20))));
+
+    // Decrypts to a final block ending in 0x00
+    let mut block = [0u8; 16];
+    KeySchedule::new(&key).encrypt_block(&mut block);
+    let mut dec = Decryptor::new(&key, Cipher::ECB, &iv);
+    assert!(dec.update(&block).is_empty());
+    assert!(matches!(dec.finalize(), Err(AesError::BadPadding)));
+
+    // Inconsistent pad bytes [.., 0x01, 0x02]
+    let mut block = [0u8; 16];
+    block[14..].copy_from_slice(&[1, 2]);
+    KeySchedule::new(&key).encrypt_block(&mut block);
+    let mut dec = Decryptor::new(&key, Cipher::ECB, &iv);
+    dec.update(&block);
+    assert!(matches!(dec.finalize(), Err(AesError::BadPadding)));
+}
+
+#[test]
+fn test_errors() {
+    let key = Key::from([0u8; 16]);
+    let iv = [0u8; 16];
+
+    assert!(matches!(
+        aes_decrypt(&key, &[0u8; 17], &Cipher::CBC, &iv),
+        Err(AesError::InvalidCiphertextLength(17))
+    ));
+
+    // CTR needs no block alignment
+    assert!(aes_decrypt(&key, &[0u8; 17], &Cipher::CTR, &iv).is_ok());
+
+    // 16 bytes of ECB plaintext needs a full pad block
+    let mut buf = [0u8; 16];
+    assert!(matches!(
+        aes_encrypt_in_place(&key, &mut buf, 16, &Cipher::ECB, &iv),
+        Err(AesError::BufferTooSmall { needed: 32, len: 16 })
+    ));
+
+    // Bad data for the std::io adapters
+    let e: io::Error = AesError::BadPadding.into();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_io_adapters() -> Result<(), Box<dyn Error>> {
+    let passkey = Key::from(*b"YELLOW SUBMARINE\0\0\0\0\0\0\0\0");
+    let ivector = hex!("000102030405060708090a0b0c0d0e0f");
+    let data: Vec<u8> = (0..100u8).collect();
+
+    for cipher in [Cipher::ECB, Cipher::CBC, Cipher::CTR] {
+        for kdf in [None, Some(Kdf::PBKDF2(1000)), Some(Kdf::ARGON2)] {
+            for (randiv, nopkcs) in [(false, false), (true, false), (false, true)] {
+                let framing = Framing {
+                    cipher,
+                    kdf: kdf.clone(),
+                    randiv,
+                    nopkcs,
+                };
+                // Argon2 is slow in debug builds
+                let lens: &[usize] = match kdf {
+                    Some(Kdf::ARGON2) => &[17],
+                    _ => &[0, 15, 16, 17, 64, 100],
+                };
+                for &len in lens {
+                    let mut w = EncryptingWriter::new(vec![], &framing, &passkey, &ivector)?;
+                    chunked(&data[..len], |chunk| {
+                        w.write_all(chunk).unwrap();
+                        vec![]
+                    });
+                    let encrypted = w.finish()?;
+
+                    // Same bytes as the CLI: 1st block followed by the ciphertext
+                    let (header, (key, iv)) = framing.encrypt_header(&passkey, &ivector)?;
+                    let mut expected = header;
+                    let ciphertext = aes_encrypt(&key, &data[..len], &cipher, &iv);
+                    let keep = match (nopkcs, cipher) {
+                        (true, Cipher::ECB | Cipher::CBC) => 16 * len.div_ceil(16),
+                        _ => ciphertext.len(),
+                    };
+                    expected.extend(&ciphertext[..keep]);
+                    assert_eq!(encrypted, expected);
+
+                    let mut r = DecryptingReader::new(&encrypted[..], &framing, &passkey, &ivector)?;
+                    let mut decrypted = vec![];
+                    r.read_to_end(&mut decrypted)?;
+                    match (nopkcs, cipher) {
+                        (true, Cipher::ECB | Cipher::CBC) => assert_eq!(&decrypted[..len], &data[..len]),
+                        _ => assert_eq!(decrypted, &data[..len]),
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_io_adapters_errors() -> Result<(), Box<dyn Error>> {
+    let framing = Framing {
+        cipher: Cipher::CBC,
+        kdf: Some(Kdf::PBKDF2(1000)),
+        randiv: false,
+        nopkcs: false,
+    };
+
+    // Missing 1st block
+    assert!(DecryptingReader::new(&b"Salted__"[..], &framing, &Key::from([0; 16]), &[0; 16]).is_err());
+
+    // Wrong passkey fails the PKCS#7 check
+    let mut w = EncryptingWriter::new(vec![], &framing, &Key::from([1; 16]), &[2; 16])?;
+    w.write_all(b"attack at dawn")?;
+    let encrypted = w.finish()?;
+    let mut r = DecryptingReader::new(&encrypted[..], &framing, &Key::from([3; 16]), &[0; 16])?;
+    let err = r.read_to_end(&mut vec![]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    Ok(())
+}
+
+// Encrypt through a small duplex pipe while decrypting from the other end
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn test_async_io_adapters() -> Result<(), Box<dyn Error>> {
+    use crate::async_io::{AsyncDecryptingReader, AsyncEncryptingWriter};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let passkey = Key::from([7u8; 32]);
+    let ivector = hex!("000102030405060708090a0b0c0d0e0f");
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+
+    for cipher in [Cipher::ECB, Cipher::CBC, Cipher::CTR] {
+        for (kdf, randiv) in [(None, false), (None, true), (Some(Kdf::PBKDF2(1000)), false)] {
+            let framing = Framing {
+                cipher,
+                kdf,
+                randiv,
+                nopkcs: false,
+            };
+
+            // Same bytes as the std::io writer
+            let mut w = EncryptingWriter::new(vec![], &framing, &passkey, &ivector)?;
+            w.write_all(&data)?;
+            let expected = w.finish()?;
+
+            let mut w = AsyncEncryptingWriter::new(vec![], &framing, &passkey, &ivector)?;
+            w.write_all(&data).await?;
+            w.shutdown().await?;
+            assert_eq!(w.into_inner(), expected);
+
+            // The pipe holds 100 bytes, both sides have to wait on each other
+            let (client, server) = tokio::io::duplex(100);
+            let write = async {
+                let mut w = AsyncEncryptingWriter::new(client, &framing, &passkey, &ivector)?;
+                for chunk in data.chunks(999) {
+                    w.write_all(chunk).await?;
+                }
+                w.shutdown().await
+            };
+            let read = async {
+                let mut r = AsyncDecryptingReader::new(server, &framing, &passkey, &ivector);
+                let mut decrypted = vec![];
+                r.read_to_end(&mut decrypted).await.map(|_| decrypted)
+            };
+            let (written, decrypted) = tokio::join!(write, read);
+            written?;
+            assert_eq!(decrypted?, data);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_input_chunks() -> Result<(), Box<dyn Error>> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    let path = std::path::PathBuf::from("src/tests/cp7.txt");
+    for (b64, hex) in [(false, false), (true, false)] {
+        let mut input = Input::open(Some(&path), b64, hex)?;
+        assert!(!input.is_empty()?);
+
+        let mut bytes = input.read_up_to(16)?;
+        loop {
+            let chunk = input.next_chunk()?;
+            if chunk.is_empty() {
+                break;
+            }
+            bytes.extend_from_slice(chunk);
+        }
+        assert!(input.is_empty()?);
+
+        let mut text = std::fs::read(&path)?;
+        if b64 {
+            text.retain(|b| !b.is_ascii_whitespace());
+            text = general_purpose::STANDARD.decode(text)?;
+        }
+        assert_eq!(bytes, text);
+    }
+    Ok(())
+}
+
+// One byte per read, groups and chunks are split across reads
+struct Trickle<'a>(&'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.0.len()).min(1);
+        buf[..n].copy_from_slice(&self.0[..n]);
+        self.0 = &self.0[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_encoding() -> Result<(), Box<dyn Error>> {
+    let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+    for (encoding, len) in [(Encoding::Base64, 1000), (Encoding::Hex, 999)] {
+        // Written in uneven pieces, wrapped at 76 columns
+        let mut writer = EncodingWriter::new(vec![], encoding);
+        for piece in data[..len].chunks(7) {
+            writer.write_all(piece)?;
+        }
+        let text = writer.finish()?;
+        assert!(text.split(|&b| b == b'\n').all(|line| line.len() <= 76));
+        assert_eq!(text.last(), Some(&b'\n'));
+
+        let mut decoded = vec![];
+        DecodingReader::new(Trickle(&text), encoding).read_to_end(&mut decoded)?;
+        assert_eq!(decoded, &data[..len]);
+    }
+
+    // Base-64 padding, then the error kinds
+    let mut decoded = vec![];
+    DecodingReader::new(Trickle(b"aGVsbG8=\n"), Encoding::Base64).read_to_end(&mut decoded)?;
+    assert_eq!(decoded, b"hello");
+    for (text, encoding) in [(&b"aGVsbG8"[..], Encoding::Base64), (b"abc", Encoding::Hex)] {
+        let e = DecodingReader::new(Trickle(text), encoding)
+            .read_to_end(&mut vec![])
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(AesError::from(e), AesError::Decode(_)));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_container() -> Result<(), Box<dyn Error>> {
+    let key = Key::new(KeySize::Aes192, b"pass word, 24 bytes long")?;
+    for (mode, kdf, encoding) in [
+        (Mode::Cipher(Cipher::ECB), None, None),
+        (
+            Mode::Cipher(Cipher::CBC),
+            Some(Kdf::PBKDF2(1000)),
+            Some(Encoding::Base64),
+        ),
+        (Mode::Cipher(Cipher::CTR), Some(Kdf::ARGON2), Some(Encoding::Hex)),
+        (Mode::Chunked, None, None),
+    ] {
+        let header = Header {
+            mode,
+            size: key.size(),
+            nopkcs: mode == Mode::Cipher(Cipher::ECB),
+            encoding,
+            kdf,
+            salt: [0x5a; 16],
+            iv: [0xa5; 16],
+        };
+        let bytes = header.to_bytes();
+        assert_eq!(bytes[..6], container::MAGIC);
+        assert_eq!(Header::from_bytes(&bytes)?, header);
+
+        // The key only depends on the password and salt
+        let derived = header.key(&key)?;
+        match header.kdf {
+            Some(ref kdf) => assert_eq!(
+                derived,
+                kdf.keyiv(key.size(), &[key.as_bytes(), &[0; 8]].concat(), &[0x5a; 16])?
+                    .0
+            ),
+            None => assert_eq!(derived, key),
+        }
+    }
+
+    // Readers reject what they don't know
+    let bytes = Header {
+        mode: Mode::Cipher(Cipher::CBC),
+        size: KeySize::Aes128,
+        nopkcs: false,
+        encoding: None,
+        kdf: Some(Kdf::ARGON2),
+        salt: [0; 16],
+        iv: [0; 16],
+    }
+    .to_bytes();
+    assert!(matches!(
+        Header::from_bytes(&bytes[..63]),
+        Err(AesError::InvalidCiphertextLength(63))
+    ));
+    for (offset, value) in [(0, b'a'), (6, 2), (7, 4), (8, 20), (9, 6), (10, 3)] {
+        let mut bad = bytes;
+        bad[offset] = value;
+        assert!(
+            matches!(Header::from_bytes(&bad), Err(AesError::Format(_))),
+            "byte {offset}"
+        );
+    }
+    let mut bad = bytes;
+    bad[15] ^= 1;
+    assert!(matches!(Header::from_bytes(&bad), Err(AesError::KdfError(_))));
+
+    // Reserved bytes are 0
+    for offset in [11, 56, 63] {
+        let mut bad = bytes;
+        bad[offset] = 1;
+        assert!(
+            matches!(Header::from_bytes(&bad), Err(AesError::Format(_))),
+            "byte {offset}"
+        );
+    }
+
+    // PBKDF2 iterations are capped, the unused parameters are 0
+    let pbkdf2 = |iter| {
+        let mut bytes = bytes;
+        bytes[10] = 1;
+        bytes[12..24].fill(0);
+        bytes[12..16].copy_from_slice(&u32::to_be_bytes(iter));
+        bytes
+    };
+    let header = Header::from_bytes(&pbkdf2(container::MAX_PBKDF2_ITER))?;
+    assert_eq!(header.kdf, Some(Kdf::PBKDF2(container::MAX_PBKDF2_ITER)));
+    assert!(matches!(
+        Header::from_bytes(&pbkdf2(container::MAX_PBKDF2_ITER + 1)),
+        Err(AesError::KdfError(_))
+    ));
+    let mut bad = pbkdf2(1000);
+    bad[23] = 1;
+    assert!(matches!(Header::from_bytes(&bad), Err(AesError::Format(_))));
+
+    // Found raw, as Base-64 or as Hex, or not at all
+    let path = std::env::temp_dir().join(format!("aes-test-container-{}", std::process::id()));
+    let body = b"0123456789abcdef0123456789";
+    let raw = [&bytes[..], body].concat();
+    let mut b64 = EncodingWriter::new(vec![], Encoding::Base64);
+    b64.write_all(&raw)?;
+    let mut hex = EncodingWriter::new(vec![], Encoding::Hex);
+    hex.write_all(&raw)?;
+    for (text, found) in [
+        (raw.clone(), true),
+        (b64.finish()?, true),
+        (hex.finish()?, true),
+        (body.to_vec(), false),
+    ] {
+        std::fs::write(&path, &text)?;
+        let (mut input, detected) = Input::open_detect(Some(&path), false, false, &container::MAGIC)?;
+        assert_eq!(detected, found);
+        let bytes = input.read_up_to(raw.len() + 1)?;
+        assert_eq!(bytes, if found { &raw[..] } else { &body[..] });
+
+        // The same from a stream
+        let (mut input, detected) = Input::detect(Box::new(io::Cursor::new(text)), false, false, &container::MAGIC)?;
+        assert_eq!(detected, found);
+        assert_eq!(
+            input.read_up_to(raw.len() + 1)?,
+            if found { &raw[..] } else { &body[..] }
+        );
+    }
+    std::fs::remove_file(&path)?;
+
+    // A path that isn't a regular file, like a FIFO or process substitution, isn't reopened:
+    // the bytes sniffed are gone from it
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+
+        let (reader, mut writer) = io::pipe()?;
+        writer.write_all(&raw)?;
+        drop(writer);
+        let path = std::path::PathBuf::from(format!("/dev/fd/{}", reader.as_raw_fd()));
+        let (mut input, detected) = Input::open_detect(Some(&path), false, false, &container::MAGIC)?;
+        assert!(detected);
+        assert_eq!(input.read_up_to(raw.len() + 1)?, raw);
+    }
+
+    // A stream is read no further than it takes to rule out the magic, a short CTR message
+    // in a pipeline decrypts without waiting for more input or EOF
+    struct Pipe(Option<Vec<u8>>);
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let data = self.0.take().expect("read past what arrived, would block");
+            buf[..data.len()].copy_from_slice(&data);
+            Ok(data.len())
+        }
+    }
+
+    let key = Key::from([7u8; 16]);
+    let iv = [1u8; 16];
+    let sealed = aes_encrypt(&key, b"hello world", &Cipher::CTR, &iv);
+    let (mut input, detected) = Input::detect(Box::new(Pipe(Some(sealed))), false, false, &container::MAGIC)?;
+    assert!(!detected);
+    let mut plaintext = vec![];
+    Decryptor::new(&key, Cipher::CTR, &iv).update_into(input.next_chunk()?, &mut plaintext);
+    assert_eq!(plaintext, b"hello world");
+    Ok(())
+}
+
+#[test]
+fn test_atomic_file() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("aes-test-atomic-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("out.txt");
+    let entries = || std::fs::read_dir(&dir).map(|d| d.count());
+
+    // Nothing is left behind without commit()
+    let mut out = AtomicFile::create(&path, false)?;
+    out.write_all(b"partial")?;
+    assert_eq!(entries()?, 1);
+    drop(out);
+    assert_eq!(entries()?, 0);
+
+    let mut out = AtomicFile::create(&path, false)?;
+    out.write_all(b"first")?;
+    out.commit()?;
+    assert_eq!(std::fs::read(&path)?, b"first");
+
+    // An existing file needs force, its permissions are kept
+    let e = AtomicFile::create(&path, false).err().expect("exists");
+    assert!(matches!(e, AesError::Io(ref e) if e.kind() == io::ErrorKind::AlreadyExists));
+
+    let mut perms = std::fs::metadata(&path)?.permissions();
+    perms.set_readonly(true);
+    std::fs::set_permissions(&path, perms.clone())?;
+    let mut out = AtomicFile::create(&path, true)?;
+    out.write_all(b"second")?;
+    out.commit()?;
+    assert_eq!(std::fs::read(&path)?, b"second");
+    assert_eq!(std::fs::metadata(&path)?.permissions(), perms);
+    assert_eq!(entries()?, 1);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_files() -> Result<(), Box<dyn Error>> {
+    use std::path::PathBuf;
+
+    // root/tree/{a.txt, b.aes, sub/{c.txt, d.txt.aes}}, and a symlinked directory
+    let root = std::env::temp_dir().join(format!("aes-test-files-{}", std::process::id()));
+    let tree = root.join("tree");
+    std::fs::create_dir_all(tree.join("sub"))?;
+    for name in ["a.txt", "b.aes", "sub/c.txt", "sub/d.txt.aes"] {
+        std::fs::write(tree.join(name), name)?;
+    }
+    #[cfg(target_family = "unix")]
+    std::os::unix::fs::symlink(tree.join("sub"), tree.join("link"))?;
+
+    let outputs = |jobs: Vec<files::Job>| -> Vec<(PathBuf, Option<PathBuf>)> {
+        jobs.into_iter().map(|(input, output)| (input, output.ok())).collect()
+    };
+    let job = |input: &str, output: Option<PathBuf>| (tree.join(input), output);
+
+    // Walked in name order, encrypting skips .aes files and decrypting takes only those
+    assert_eq!(
+        outputs(files::plan(std::slice::from_ref(&tree), true, true, None, false)),
+        [
+            job("a.txt", Some(tree.join("a.txt.aes"))),
+            job("sub/c.txt", Some(tree.join("sub/c.txt.aes"))),
+        ]
+    );
+    assert_eq!(
+        outputs(files::plan(std::slice::from_ref(&tree), false, true, None, false)),
+        [
+            job("b.aes", Some(tree.join("b"))),
+            job("sub/d.txt.aes", Some(tree.join("sub/d.txt"))),
+        ]
+    );
+
+    // --out-dir mirrors the directory named, a file lands directly below it
+    let out = root.join("out");
+    assert_eq!(
+        outputs(files::plan(
+            &[tree.clone(), tree.join("sub/c.txt")],
+            true,
+            true,
+            Some(&out),
+            false
+        )),
+        [
+            job("a.txt", Some(out.join("tree/a.txt.aes"))),
+            job("sub/c.txt", Some(out.join("tree/sub/c.txt.aes"))),
+            job("sub/c.txt", Some(out.join("c.txt.aes"))),
+        ]
+    );
+
+    // In place, the input itself
+    assert_eq!(
+        outputs(files::plan(&[tree.join("a.txt")], true, false, None, true)),
+        [job("a.txt", Some(tree.join("a.txt").canonicalize()?))]
+    );
+
+    // -R in place takes every file both ways, names don't change, so decrypting finds what
+    // encrypting wrote
+    let key = Key::from([7u8; 16]);
+    let iv = [1u8; 16];
+    let every = ["a.txt", "b.aes", "sub/c.txt", "sub/d.txt.aes"];
+    for encrypt in [true, false] {
+        let jobs = files::plan(std::slice::from_ref(&tree), encrypt, true, None, true);
+        let inputs: Vec<PathBuf> = jobs.iter().map(|(input, _)| input.clone()).collect();
+        assert_eq!(inputs, every.map(|name| tree.join(name)));
+        for (input, output) in jobs {
+            let data = std::fs::read(&input)?;
+            let data = match encrypt {
+                true => aes_encrypt(&key, &data, &Cipher::CTR, &iv),
+                false => aes_decrypt(&key, &data, &Cipher::CTR, &iv)?,
+            };
+            std::fs::write(output?, data)?;
+        }
+    }
+    for name in every {
+        assert_eq!(std::fs::read(tree.join(name))?, name.as_bytes());
+    }
+
+    // Bad paths in the middle fail on their own: a directory without -R, stdin, no .aes to strip
+    let jobs = files::plan(
+        &[
+            tree.join("b.aes"),
+            tree.clone(),
+            PathBuf::from("-"),
+            tree.join("a.txt"),
+            tree.join("sub/d.txt.aes"),
+        ],
+        false,
+        false,
+        None,
+        false,
+    );
+    let failed: Vec<bool> = jobs.iter().map(|(_, output)| output.is_err()).collect();
+    assert_eq!(failed, [false, true, true, true, false]);
+    for (_, output) in &jobs[1..4] {
+        assert!(matches!(output, Err(AesError::InvalidArgument(_))));
+    }
+
+    // ... which --out-dir keeps
+    assert_eq!(
+        outputs(files::plan(&[tree.join("a.txt")], false, false, Some(&out), false)),
+        [job("a.txt", Some(out.join("a.txt")))]
+    );
+
+    // An output that exists isn't replaced without force
+    let (_, output) = &files::plan(&[tree.join("sub/d.txt.aes")], false, false, None, false)[0];
+    std::fs::write(tree.join("sub/d.txt"), "exists")?;
+    let e = AtomicFile::create(output.as_ref().unwrap(), false)
+        .err()
+        .expect("exists");
+    assert!(matches!(e, AesError::Io(ref e) if e.kind() == io::ErrorKind::AlreadyExists));
+    assert_eq!(std::fs::read(tree.join("sub/d.txt"))?, b"exists");
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[test]
+fn test_gcm() {
+    // McGrew & Viega test cases 1, 2 and 4 (with associated data)
+    let key = KeySchedule::new(&Key::from([0u8; 16]));
+    let mut empty = [];
+    assert_eq!(
+        gcm::encrypt(&key, &[0; 12], &[], &mut empty),
+        hex!("58e2fccefa7e3061367f1d57a4e7455a")
+    );
+
+    let mut buf = [0u8; 16];
+    let tag = gcm::encrypt(&key, &[0; 12], &[], &mut buf);
+    assert_eq!(buf, hex!("0388dace60b6a392f328c2b971b2fe78"));
+    assert_eq!(tag, hex!("ab6e47d42cec13bdf53a67b21257bddf"));
+
+    let key = KeySchedule::new(&Key::from(hex!("feffe9928665731c6d6a8f9467308308")));
+    let nonce = hex!("cafebabefacedbaddecaf888");
+    let aad = hex!("feedfacedeadbeeffeedfacedeadbeefabaddad2");
+    let plaintext = hex!(
+        "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72"
+        "1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39"
+    );
+    let mut buf = plaintext;
+    let tag = gcm::encrypt(&key, &nonce, &aad, &mut buf);
     assert_eq!(
-        passkey,
-        hex!("416C6C6D616E2042726F74686572732100000000000000000000000000000000")
+        buf,
+        hex!(
+            "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e"
+            "21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091"
+        )
     );
+    assert_eq!(tag, hex!("5bc94fbc3221a5db94fae95ae7121a47"));
+
+    // Tampering with the ciphertext, associated data or tag leaves "buf" as it was
+    let sealed = buf;
+    for (i, j) in [(0, 0), (1, 0), (2, 0), (0, 59)] {
+        let (mut buf, mut aad, mut tag) = (sealed, aad, tag);
+        match i {
+            0 => buf[j] ^= 1,
+            1 => aad[j] ^= 1,
+            _ => tag[j] ^= 1,
+        }
+        let before = buf;
+        assert!(matches!(
+            gcm::decrypt(&key, &nonce, &aad, &mut buf, &tag),
+            Err(AesError::AuthenticationFailed)
+        ));
+        assert_eq!(buf, before);
+    }
+
+    gcm::decrypt(&key, &nonce, &aad, &mut buf, &tag).unwrap();
+    assert_eq!(buf, plaintext);
+}
+
+#[test]
+fn test_chunked() -> Result<(), Box<dyn Error>> {
+    let key = Key::new(KeySize::Aes256, &[7; 32])?;
+    let prefix = [1, 2, 3, 4, 5, 6, 7];
+    let aad = b"header";
+    let seal = |data: &[u8]| -> io::Result<Vec<u8>> {
+        let mut writer = ChunkedWriter::new(vec![], &key, &prefix, aad);
+        writer.write_all(data)?;
+        writer.finish()
+    };
+    let open = |sealed: &[u8]| -> io::Result<Vec<u8>> {
+        let mut plain = vec![];
+        ChunkedReader::new(sealed, &key, &prefix, aad).read_to_end(&mut plain)?;
+        Ok(plain)
+    };
+
+    // A tag per chunk, the last may be empty only when there's no data
+    let size = chunked::CHUNK_SIZE;
+    let data: Vec<u8> = (0..3 * size + 100).map(|i| (i % 251) as u8).collect();
+    for (len, chunks) in [(0, 1), (1, 1), (size, 1), (size + 1, 2), (3 * size + 100, 4)] {
+        let sealed = seal(&data[..len])?;
+        assert_eq!(sealed.len(), len + 16 * chunks, "{len} bytes");
+        assert_eq!(open(&sealed)?, &data[..len], "{len} bytes");
+    }
+
+    // Written a byte at a time, read back through a 1-byte reader
+    let sealed = seal(&data)?;
+    let mut writer = ChunkedWriter::new(vec![], &key, &prefix, aad);
+    for byte in &data {
+        writer.write_all(&[*byte])?;
+    }
+    assert_eq!(writer.finish()?, sealed);
+    let mut plain = vec![];
+    ChunkedReader::new(Trickle(&sealed[..]), &key, &prefix, aad).read_to_end(&mut plain)?;
+    assert_eq!(plain, data);
+
+    // Seeking only decrypts the chunks read, the first one here is damaged
+    let mut damaged = sealed.clone();
+    damaged[0] ^= 1;
+    let mut reader = ChunkedReader::new(io::Cursor::new(&damaged), &key, &prefix, aad);
+    assert_eq!(reader.seek(SeekFrom::Start(size as u64 + 10))?, size as u64 + 10);
+    let mut buf = vec![0; 2 * size];
+    reader.read_exact(&mut buf)?;
+    assert_eq!(buf, &data[size + 10..3 * size + 10]);
+    assert_eq!(reader.seek(SeekFrom::End(-5))?, data.len() as u64 - 5);
+    assert_eq!(reader.read(&mut buf)?, 5);
+    assert_eq!(buf[..5], data[data.len() - 5..]);
+    assert_eq!(reader.read(&mut buf)?, 0);
+    reader.seek(SeekFrom::Current(-(size as i64)))?;
+    reader.read_exact(&mut buf[..size])?;
+    assert_eq!(buf[..size], data[data.len() - size..]);
+    reader.rewind()?;
+    assert!(reader.read(&mut buf).is_err());
+
+    // Seeking after a sequential read, from a header
+    let mut file = b"header".to_vec();
+    file.extend_from_slice(&sealed);
+    let mut cursor = io::Cursor::new(&file);
+    cursor.seek(SeekFrom::Start(6))?;
+    let mut reader = ChunkedReader::new(cursor, &key, &prefix, aad);
+    reader.read_exact(&mut buf[..10])?;
+    assert_eq!(reader.seek(SeekFrom::Current(2 * size as i64))?, 2 * size as u64 + 10);
+    reader.read_exact(&mut buf[..10])?;
+    assert_eq!(buf[..10], data[2 * size + 10..2 * size + 20]);
+
+    // Truncated, reordered, swapped, modified or under other associated data, nothing is accepted
+    let full = size + 16;
+    let mut reordered = sealed.clone();
+    reordered[..2 * full].rotate_left(full);
+    let mut other = ChunkedWriter::new(vec![], &key, &[0; 7], aad);
+    other.write_all(&data)?;
+    let other = other.finish()?;
+    let mut swapped = sealed.clone();
+    swapped[full..2 * full].copy_from_slice(&other[full..2 * full]);
+    let mut modified = sealed.clone();
+    modified[sealed.len() - 1] ^= 1;
+    for (what, bad) in [
+        ("cut at a chunk", &sealed[..3 * full]),
+        ("cut in a chunk", &sealed[..3 * full + 50]),
+        ("reordered", &reordered[..]),
+        ("swapped", &swapped[..]),
+        ("modified", &modified[..]),
+    ] {
+        let e = open(bad).expect_err(what);
+        assert!(matches!(AesError::from(e), AesError::AuthenticationFailed), "{what}");
+    }
+    let mut plain = vec![];
+    let e = ChunkedReader::new(&sealed[..], &key, &prefix, b"other").read_to_end(&mut plain);
+    assert!(matches!(AesError::from(e.unwrap_err()), AesError::AuthenticationFailed));
+    assert!(plain.is_empty());
+
+    // Nothing but a partial tag, or no last chunk at all
+    assert!(matches!(
+        AesError::from(open(&sealed[..10]).unwrap_err()),
+        AesError::InvalidCiphertextLength(10)
+    ));
+    assert!(open(&[]).is_err());
+
+    // Dropped without finish(), the output can't be mistaken for a complete stream
+    let mut out = vec![];
+    ChunkedWriter::new(&mut out, &key, &prefix, aad).write_all(&data)?;
+    assert!(open(&out).is_err());
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_encrypted() -> Result<(), Box<dyn Error>> {
+    use crate::encrypted::{with_key, Encrypted};
+    use base64::{engine::general_purpose, Engine as _};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        user: String,
+        password: Encrypted<String>,
+        ports: Encrypted<Vec<u16>>,
+    }
+
+    let key = Key::from(*b"YELLOW SUBMARINEyellow submarine");
+    let config = Config {
+        user: "dave".into(),
+        password: Encrypted::new("hunter2".into()),
+        ports: Encrypted::new(vec![22, 443]),
+    };
+
+    // JSON and TOML, only the Encrypted fields are opaque
+    let json = with_key(&key, || serde_json::to_string(&config))?;
+    assert!(json.contains("\"user\":\"dave\"") && !json.contains("hunter2"));
+    assert_eq!(with_key(&key, || serde_json::from_str::<Config>(&json))?, config);
+
+    let toml = with_key(&key, || toml::to_string(&config))?;
+    assert!(toml.contains("user = \"dave\"") && !toml.contains("hunter2"));
+    assert_eq!(with_key(&key, || toml::from_str::<Config>(&toml))?, config);
+    assert_eq!(format!("{:?}", config.password), "Encrypted(..)");
+
+    // A fresh nonce each time
+    let sealed = config.password.seal(&key)?;
+    assert_ne!(sealed, config.password.seal(&key)?);
+    assert_eq!(Encrypted::<String>::open(&key, &sealed)?.into_inner(), "hunter2");
+
+    // Wrong key, edited string, no key
+    let other = Key::from([0u8; 32]);
+    assert!(matches!(
+        Encrypted::<String>::open(&other, &sealed),
+        Err(AesError::AuthenticationFailed)
+    ));
+    let err = with_key(&other, || serde_json::from_str::<Config>(&json)).unwrap_err();
+    assert!(err.to_string().contains("authentication failed"));
+
+    let mut edited = general_purpose::STANDARD.decode(&sealed)?;
+    edited[12] ^= 1;
+    let edited = general_purpose::STANDARD.encode(edited);
+    assert!(Encrypted::<String>::open(&key, &edited).is_err());
+
+    assert!(serde_json::to_string(&config)
+        .unwrap_err()
+        .to_string()
+        .contains("needs a key"));
+    assert!(serde_json::from_str::<Config>(&json).is_err());
+
+    // The previous key comes back after a nested with_key()
+    with_key(&other, || {
+        with_key(&key, || serde_json::from_str::<Config>(&json)).unwrap();
+        assert!(serde_json::from_str::<Config>(&json).is_err());
+    });
     Ok(())
 }
 
+// Cross-validate against the RustCrypto mode crates
+#[cfg(feature = "cipher")]
+#[test]
+fn test_rustcrypto_traits() {
+    use crate::rustcrypto::{Aes128, Aes192, Aes256};
+    use ::cbc::cipher::block_padding::Pkcs7;
+    use ::cbc::cipher::{BlockDecrypt, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyIvInit, StreamCipher};
+    use aes_gcm::aead::{Aead, KeyInit as _};
+
+    // FIPS-197 Appendix C.1
+    let key = Aes128::new(&hex!("000102030405060708090a0b0c0d0e0f").into());
+    let mut block = hex!("00112233445566778899aabbccddeeff").into();
+    key.encrypt_block(&mut block);
+    assert_eq!(block[..], hex!("69c4e0d86a7b0430d8cdb78070b4c55a"));
+    key.decrypt_block(&mut block);
+    assert_eq!(block[..], hex!("00112233445566778899aabbccddeeff"));
+
+    let passkey = *b"YELLOW SUBMARINEyellow submarine";
+    let iv = hex!("000102030405060708090a0b0c0d0e0f");
+    let data: Vec<u8> = (0..200u8).collect();
+
+    // CBC with PKCS#7, exercising the 4-block parallel backend on decrypt
+    let encrypted =
+        ::cbc::Encryptor::<Aes192>::new(passkey[..24].into(), &iv.into()).encrypt_padded_vec_mut::<Pkcs7>(&data);
+    let passkey192 = Key::new(KeySize::Aes192, &passkey[..24]).unwrap();
+    assert_eq!(encrypted, aes_encrypt(&passkey192, &data, &Cipher::CBC, &iv));
+    let decrypted = ::cbc::Decryptor::<Aes192>::new(passkey[..24].into(), &iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(&encrypted)
+        .unwrap();
+    assert_eq!(decrypted, data);
+
+    // CTR: 8-byte nonce and 64-bit big-endian counter
+    let mut buf = data.clone();
+    ::ctr::Ctr64BE::<Aes256>::new(&passkey.into(), &iv.into()).apply_keystream(&mut buf);
+    assert_eq!(buf, aes_encrypt(&Key::from(passkey), &data, &Cipher::CTR, &iv));
+
+    // GCM test case 2 (McGrew & Viega), key 0^128, iv 0^96, plaintext 0^128
+    let gcm = aes_gcm::AesGcm::<Aes128, aes_gcm::aead::consts::U12>::new(&[0u8; 16].into());
+    let sealed = gcm.encrypt(&[0u8; 12].into(), &[0u8; 16][..]).unwrap();
+    assert_eq!(
+        sealed,
+        hex!("0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf")
+    );
+
+    // modes::gcm against aes-gcm, every tail length and associated data
+    use aes_gcm::aead::Payload;
+    let gcm = aes_gcm::AesGcm::<Aes256, aes_gcm::aead::consts::U12>::new(&passkey.into());
+    let key = KeySchedule::new(&Key::from(passkey));
+    let nonce = hex!("000102030405060708090a0b");
+    for len in 0..data.len() {
+        let aad = &data[..len % 37];
+        let sealed = gcm.encrypt(&nonce.into(), Payload { msg: &data[..len], aad }).unwrap();
+        let mut buf = data[..len].to_vec();
+        let tag = gcm::encrypt(&key, &nonce, aad, &mut buf);
+        assert_eq!(sealed, [buf, tag.to_vec()].concat());
+    }
+}
+
+// An 8-byte toy block cipher: XOR with the key then rotate the block left one byte
+struct Toy([u8; 8]);
+
+impl BlockCipher for Toy {
+    const BLOCK_SIZE: usize = 8;
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        for (b, k) in block.iter_mut().zip(self.0) {
+            *b ^= k;
+        }
+        block.rotate_left(1);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        block.rotate_right(1);
+        for (b, k) in block.iter_mut().zip(self.0) {
+            *b ^= k;
+        }
+    }
+}
+
+#[test]
+fn test_modes_generic() {
+    let toy = Toy(*b"toy key!");
+    let iv = *b"\x01\x02\x03\x04\x05\x06\xff\xff";
+    let data = *b"0123456789abcdef01234567";
+
+    // ECB: identical blocks encrypt identically
+    let mut buf = *b"abcdefghabcdefgh";
+    ecb::encrypt(&toy, &mut buf);
+    assert_eq!(buf[..8], buf[8..]);
+    ecb::decrypt(&toy, &mut buf);
+    assert_eq!(&buf, b"abcdefghabcdefgh");
+
+    // CBC: C[n] = E(P[n] ^ C[n-1])
+    let mut buf = data;
+    cbc::encrypt(&toy, &mut buf, &iv);
+    let mut expected = data;
+    let mut chain = iv;
+    for block in expected.chunks_exact_mut(8) {
+        block.iter_mut().zip(chain).for_each(|(b, c)| *b ^= c);
+        toy.encrypt_block(block);
+        chain.copy_from_slice(block);
+    }
+    assert_eq!(buf, expected);
+    cbc::decrypt(&toy, &mut buf, &iv);
+    assert_eq!(buf, data);
+
+    // CTR: the counter is the last 8 bytes (the whole toy block), carrying across bytes
+    let mut buf = [0u8; 20];
+    let next = ctr::apply(&toy, &mut buf, &iv);
+    assert_eq!(next, 0x0102030405070002);
+    let mut block = iv;
+    toy.encrypt_block(&mut block);
+    assert_eq!(buf[..8], block);
+    let mut block = 0x0102030405070001u64.to_be_bytes();
+    toy.encrypt_block(&mut block);
+    assert_eq!(buf[16..], block[..4]);
+
+    // ... or only the last 4 bytes, wrapping without a carry (GCM's inc32)
+    let iv = *b"\x01\x02\x03\x04\xff\xff\xff\xff";
+    let mut buf = [0u8; 16];
+    assert_eq!(ctr::apply32(&toy, &mut buf, &iv), 1);
+    let mut block = *b"\x01\x02\x03\x04\0\0\0\0";
+    toy.encrypt_block(&mut block);
+    assert_eq!(buf[8..], block);
+
+    // PKCS#7 for an 8-byte block
+    let mut block = *b"abc\0\0\0\0\0";
+    pkcs7::pad(&mut block, 3);
+    assert_eq!(&block, b"abc\x05\x05\x05\x05\x05");
+    assert_eq!(pkcs7::padcount(&block), Some(5));
+    assert_eq!(pkcs7::padcount(b"abc\x05\x05\x05\x04\x05"), None);
+    assert_eq!(pkcs7::padcount(b"abcdefg\x09"), None);
+    assert_eq!(pkcs7::padcount(b"abcdefg\x00"), None);
+    assert_eq!(pkcs7::padcount(&[8; 8]), Some(8));
+
+    // Every count on a 16-byte block, and the first pad byte wrong
+    for n in 0..=255u8 {
+        let mut block = [n; 16];
+        let expected = (1..=16).contains(&n).then_some(n as usize);
+        assert_eq!(pkcs7::padcount(&block), expected, "{n}");
+        if (2..=16).contains(&n) {
+            block[16 - n as usize] ^= 1;
+            assert_eq!(pkcs7::padcount(&block), None, "{n}");
+        }
+    }
+}
+
 #[test]
 fn test_cryptopals() -> Result<(), Box<dyn Error>> {
     let expected = b"I'm back and I'm ringin' the bell 
@@ -282,10 +1517,11 @@ Play that funky music, white boy Come on, Come on, Come on
 Play that funky music 
 ";
     let b64_encoded = true;
-    let msg = read_input_bytes(Some(&std::path::PathBuf::from("src/tests/cp7.txt")), b64_encoded, false)?;
+    let mut msg = vec![];
+    Input::open(Some(&std::path::PathBuf::from("src/tests/cp7.txt")), b64_encoded, false)?.read_to_end(&mut msg)?;
     let hexkey = "59454c4c4f57205355424d4152494e45".to_string();
-    let (bits, passkey) = get_passkey32(None, None, Some(&hexkey), true)?;
-    let out = aes_decrypt(bits, &passkey, &msg, &Cipher::ECB, &[0u8; 16]);
+    let passkey = get_key(None, None, Some(&hexkey), true)?;
+    let out = aes_decrypt(&passkey, &msg, &Cipher::ECB, &[0u8; 16])?;
     // aes_decrypt() doesn't perform pad char removal, so test up to the length of expected
     assert_eq!(out[..(expected.len())].to_vec(), expected);
     Ok(())